@@ -15,13 +15,17 @@ use panic_halt as _;
 #[cfg(feature = "defmt")]
 use {defmt_rtt as _, panic_probe as _};
 
-use hal::pac::{self, interrupt};
+use hal::pac;
 use stm32h7xx_hal as hal;
 
-use daisy::audio;
+// Daisy Patch passes frames of both of its codecs, four channels in total.
+#[cfg(feature = "patch")]
+use daisy::audio::patch::{Frame, Interface};
+#[cfg(not(feature = "patch"))]
+use daisy::audio::{Interface, interface::Frame};
 
 // Keep audio interface in a global, so it can be shared between functions.
-static AUDIO_INTERFACE: Mutex<RefCell<Option<audio::Interface>>> = Mutex::new(RefCell::new(None));
+static AUDIO_INTERFACE: Mutex<RefCell<Option<Interface>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
@@ -62,22 +66,34 @@ fn main() -> ! {
 
 // Audio is tranfered from the input and to the input periodically thorugh DMA.
 // Every time Daisy is done transferring data, it will ask for more by triggering
-// the DMA 1 Stream 1 interrupt.
-#[interrupt]
-fn DMA1_STR1() {
+// the audio interrupt.
+daisy::audio_interrupt!({
     cortex_m::interrupt::free(|cs| {
         // Acquire the audio interface from the global.
         if let Some(audio_interface) = AUDIO_INTERFACE.borrow(cs).borrow_mut().as_mut() {
             // Read input audio from the buffer and write back desired
             // output samples.
             audio_interface
-                .handle_interrupt_dma1_str1(|audio_buffer| {
+                .handle_interrupt(|audio_buffer| {
                     for frame in audio_buffer {
-                        let (left, right) = *frame;
-                        *frame = (right * 0.8, left * 0.8);
+                        swap_channels(frame);
                     }
                 })
                 .unwrap();
         }
     });
+});
+
+// Swap left and right channels, attenuating them slightly.
+#[cfg(not(feature = "patch"))]
+fn swap_channels(frame: &mut Frame) {
+    let (left, right) = *frame;
+    *frame = (right * 0.8, left * 0.8);
+}
+
+// Swap left and right channels of each codec.
+#[cfg(feature = "patch")]
+fn swap_channels(frame: &mut Frame) {
+    let [left_a, right_a, left_b, right_b] = *frame;
+    *frame = [right_a * 0.8, left_a * 0.8, right_b * 0.8, left_b * 0.8];
 }
@@ -11,11 +11,17 @@ use panic_halt as _;
 #[cfg(feature = "defmt")]
 use {defmt_rtt as _, panic_probe as _};
 
+// Daisy Patch passes frames of both of its codecs, four channels in total.
+#[cfg(feature = "patch")]
+use daisy::audio::patch::{Frame, Interface};
+#[cfg(not(feature = "patch"))]
+use daisy::audio::{Interface, interface::Frame};
+
 #[rtic::app(device = stm32h7xx_hal::pac, peripherals = true)]
 mod app {
     use systick_monotonic::*;
 
-    use daisy::audio::Interface;
+    use super::{Interface, swap_channels};
 
     #[monotonic(binds = SysTick, default = true)]
     type Mono = Systick<1000>; // 1 kHz / 1 ms granularity
@@ -59,18 +65,33 @@ mod app {
 
     // Audio is tranfered from the input and to the input periodically thorugh DMA.
     // Every time Daisy is done transferring data, it will ask for more by triggering
-    // the DMA 1 Stream 1 interrupt.
+    // the audio interrupt. RTIC needs its name spelled out, verify it matches.
+    daisy::assert_audio_interrupt!(DMA1_STR1);
+
     #[task(binds = DMA1_STR1, local = [audio_interface])]
     fn dsp(cx: dsp::Context) {
         let audio_interface = cx.local.audio_interface;
 
         audio_interface
-            .handle_interrupt_dma1_str1(|audio_buffer| {
+            .handle_interrupt(|audio_buffer| {
                 for frame in audio_buffer {
-                    let (left, right) = *frame;
-                    *frame = (right * 0.8, left * 0.8);
+                    swap_channels(frame);
                 }
             })
             .unwrap();
     }
 }
+
+// Swap left and right channels, attenuating them slightly.
+#[cfg(not(feature = "patch"))]
+fn swap_channels(frame: &mut Frame) {
+    let (left, right) = *frame;
+    *frame = (right * 0.8, left * 0.8);
+}
+
+// Swap left and right channels of each codec.
+#[cfg(feature = "patch")]
+fn swap_channels(frame: &mut Frame) {
+    let [left_a, right_a, left_b, right_b] = *frame;
+    *frame = [right_a * 0.8, left_a * 0.8, right_b * 0.8, left_b * 0.8];
+}
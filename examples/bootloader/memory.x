@@ -15,3 +15,15 @@ MEMORY
 
 REGION_ALIAS(RAM, DTCMRAM);
 REGION_ALIAS("FLASH", SRAM);
+
+SECTIONS
+{
+	/* Audio DMA buffers, kept in the region the bootloader reserves for DMA. */
+	.sram1_bss (NOLOAD) :
+	{
+		. = ALIGN(4);
+		*(.sram1_bss)
+		*(.sram1_bss*)
+		. = ALIGN(4);
+	} > RAM_D2_DMA
+}
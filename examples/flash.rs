@@ -41,15 +41,23 @@ fn main() -> ! {
 
     // Write it to the flash memory.
     log!("Writting to flash");
-    flash.write(ADDRESS, &data);
+    flash.write(ADDRESS, &data).unwrap();
 
     // Read it back.
     log!("Reading from flash");
     let mut buffer: [u8; SIZE] = [0; SIZE];
-    flash.read(ADDRESS, &mut buffer);
+    flash.read(ADDRESS, &mut buffer).unwrap();
+
+    // Read it once more, directly from the memory-mapped flash.
+    log!("Reading from memory-mapped flash");
+    let flash = flash
+        .into_memory_mapped()
+        .map_err(|(_, error)| error)
+        .unwrap();
+    let mapped = &flash.data()[ADDRESS as usize..ADDRESS as usize + SIZE];
 
     // Compare the read values with those written and lit the LED if they match.
-    if data == buffer {
+    if data == buffer && data == mapped {
         log!("Everything went as expected");
         led_user.set_high();
     } else {
@@ -0,0 +1,81 @@
+//! Example of how to configure four-channel audio of Daisy Patch and
+//! implement a basic passthrough rotating the channels.
+
+#![no_main]
+#![no_std]
+
+use core::cell::RefCell;
+
+use cortex_m::asm;
+use cortex_m::interrupt::Mutex;
+use cortex_m_rt::entry;
+
+#[cfg(not(feature = "defmt"))]
+use panic_halt as _;
+#[cfg(feature = "defmt")]
+use {defmt_rtt as _, panic_probe as _};
+
+use hal::pac;
+use stm32h7xx_hal as hal;
+
+use daisy::audio;
+
+// Keep audio interface in a global, so it can be shared between functions.
+static AUDIO_INTERFACE: Mutex<RefCell<Option<audio::patch::Interface>>> =
+    Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    // Get core and device peripherals.
+    let mut cp = cortex_m::Peripherals::take().unwrap();
+    let dp = pac::Peripherals::take().unwrap();
+
+    // Using caches should provide a major performance boost.
+    cp.SCB.enable_icache();
+    // NOTE: Data caching requires cache management around all use of DMA.
+    // This crate already handles that for audio processing.
+    cp.SCB.enable_dcache(&mut cp.CPUID);
+
+    // Initialize the board abstraction.
+    let board = daisy::Board::take().unwrap();
+
+    // Configure board's peripherals. Audio interface of the Patch covers
+    // both the codec of the Seed and the external one.
+    let ccdr = daisy::board_freeze_clocks!(board, dp);
+    let pins = daisy::board_split_gpios!(board, ccdr, dp);
+    let mut led_user = daisy::board_split_leds!(pins).USER;
+    let audio_interface = daisy::board_split_audio!(ccdr, pins);
+
+    // Start audio processing and put its abstraction into a global.
+    let audio_interface = audio_interface.spawn().unwrap();
+    cortex_m::interrupt::free(|cs| {
+        AUDIO_INTERFACE.borrow(cs).replace(Some(audio_interface));
+    });
+
+    // Keep blinking to block main and shows signs of life.
+    let one_second = ccdr.clocks.sys_ck().to_Hz();
+    loop {
+        led_user.toggle();
+        asm::delay(one_second);
+        led_user.toggle();
+        asm::delay(one_second);
+    }
+}
+
+// Both codecs are served from the interrupt of the on-board one.
+daisy::audio_interrupt!({
+    cortex_m::interrupt::free(|cs| {
+        // Acquire the audio interface from the global.
+        if let Some(audio_interface) = AUDIO_INTERFACE.borrow(cs).borrow_mut().as_mut() {
+            // Read input audio of all four channels from the buffer and
+            // write back desired output samples.
+            audio_interface
+                .handle_interrupt(|audio_buffer| {
+                    for frame in audio_buffer {
+                        frame.rotate_left(1);
+                    }
+                })
+                .unwrap();
+        }
+    });
+});
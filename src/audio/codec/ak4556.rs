@@ -1,24 +1,34 @@
 use cortex_m::asm;
 
+use super::CodecControl;
+use crate::audio::interface::Error;
 use crate::hal;
 use hal::gpio;
+use hal::hal::digital::v2::OutputPin;
 
 type ResetPin = gpio::gpiob::PB11<gpio::Output<gpio::PushPull>>; // PDN
 
-pub type Pins = (ResetPin,);
+pub type Pins<R = ResetPin> = (R,);
 
-pub struct Codec {
-    reset: ResetPin,
+/// Driver of AK4556, generic over its reset pin, so it can serve both the
+/// on-board codec of Seed and the external one of Patch.
+pub struct Codec<R = ResetPin> {
+    reset: R,
 }
 
-impl Codec {
-    pub fn init(pins: Pins) -> Self {
+impl<R: OutputPin> Codec<R> {
+    pub fn init(pins: Pins<R>) -> Self {
         Self { reset: pins.0 }
     }
 
-    pub fn start(&mut self) {
-        self.reset.set_low();
+    pub fn start(&mut self) -> Result<(), Error> {
+        // GPIO of the HAL is infallible.
+        let _ = self.reset.set_low();
         asm::delay(480_000); // ~ 1ms (datasheet specifies minimum 150ns)
-        self.reset.set_high();
+        let _ = self.reset.set_high();
+        Ok(())
     }
 }
+
+// AK4556 does not offer any runtime control.
+impl<R: OutputPin> CodecControl for Codec<R> {}
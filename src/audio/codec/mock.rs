@@ -0,0 +1,75 @@
+//! I2C bus recording transactions, used to test register sequences of codecs
+//! on the host.
+
+extern crate std;
+
+use std::vec::Vec;
+
+use crate::hal::hal::blocking::i2c::{Write, WriteRead};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Transaction {
+    Write(u8, Vec<u8>),
+    WriteRead(u8, Vec<u8>),
+}
+
+/// Mock of an I2C bus. Writes of a register address followed by a value
+/// update the emulated register map, reads return values from it.
+pub struct MockI2c {
+    pub transactions: Vec<Transaction>,
+    pub registers: [u8; 256],
+    /// Number of transactions to succeed before the bus starts failing.
+    pub fail_after: Option<usize>,
+}
+
+impl MockI2c {
+    pub fn new() -> Self {
+        Self {
+            transactions: Vec::new(),
+            registers: [0; 256],
+            fail_after: None,
+        }
+    }
+
+    /// Bytes of all write transactions, in order.
+    pub fn writes(&self) -> Vec<&[u8]> {
+        self.transactions
+            .iter()
+            .filter_map(|t| match t {
+                Transaction::Write(_, bytes) => Some(bytes.as_slice()),
+                Transaction::WriteRead(..) => None,
+            })
+            .collect()
+    }
+
+    fn record(&mut self, transaction: Transaction) -> Result<(), ()> {
+        if self.fail_after == Some(self.transactions.len()) {
+            return Err(());
+        }
+        self.transactions.push(transaction);
+        Ok(())
+    }
+}
+
+impl Write for MockI2c {
+    type Error = ();
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+        self.record(Transaction::Write(address, bytes.to_vec()))?;
+        if let [register, value] = bytes {
+            self.registers[*register as usize] = *value;
+        }
+        Ok(())
+    }
+}
+
+impl WriteRead for MockI2c {
+    type Error = ();
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), ()> {
+        self.record(Transaction::WriteRead(address, bytes.to_vec()))?;
+        let register = bytes[0] as usize;
+        buffer.copy_from_slice(&self.registers[register..register + buffer.len()]);
+        Ok(())
+    }
+}
@@ -1,12 +1,60 @@
-#[cfg(feature = "seed")]
+use crate::audio::interface::Error;
+
+#[cfg(all(test, any(feature = "seed_1_1", feature = "patch_sm")))]
+mod mock;
+
+/// Runtime control of the audio codec, accessible through
+/// [`Interface::codec`](crate::audio::Interface::codec).
+///
+/// Settings that the codec of the board does not offer return
+/// [`Error::Unsupported`]. Settings are kept by the driver and applied again
+/// whenever the codec gets started by
+/// [`Interface::spawn`](crate::audio::Interface::spawn) or
+/// [`Interface::restart`](crate::audio::Interface::restart), so they can be
+/// changed while the streaming is stopped as well.
+pub trait CodecControl {
+    /// Set the gain of line inputs in decibels. The value gets clamped to the
+    /// range supported by the codec.
+    fn set_input_gain(&mut self, _gain_db: f32) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// Set the attenuation of line outputs in decibels, positive values lower
+    /// the volume. The value gets clamped to the range supported by the codec.
+    ///
+    /// Line outputs of WM8731 have a fixed level, it attenuates the headphone
+    /// output instead and is unsupported unless the headphone is enabled.
+    fn set_output_attenuation(&mut self, _attenuation_db: f32) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// Gradually mute or unmute the output.
+    fn set_mute(&mut self, _mute: bool) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// Enable the de-emphasis filter on the output.
+    fn set_deemphasis(&mut self, _enabled: bool) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// Put the ADC and DAC into power-save mode.
+    fn set_power_save(&mut self, _adc: bool, _dac: bool) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(any(feature = "seed", feature = "patch"))]
 mod ak4556;
+#[cfg(feature = "patch")]
+pub use ak4556::Codec as Ak4556;
 #[cfg(feature = "seed")]
 pub use ak4556::{Codec, Pins};
 
 #[cfg(feature = "seed_1_1")]
 mod wm8731;
 #[cfg(feature = "seed_1_1")]
-pub use wm8731::{Codec, Pins};
+pub use wm8731::{AnalogOutput, Codec, Headphone, Pins, Sidetone};
 
 #[cfg(feature = "seed_1_2")]
 mod pcm3060_parallel;
@@ -17,3 +65,13 @@ pub use pcm3060_parallel::{Codec, Pins};
 mod pcm3060_i2c;
 #[cfg(feature = "patch_sm")]
 pub use pcm3060_i2c::{Codec, Pins};
+
+/// Busy wait between register writes, giving the codec time to settle.
+/// Skipped on the host, where drivers are tested against a mock bus.
+#[cfg(any(feature = "seed_1_1", feature = "patch_sm"))]
+fn delay(cycles: u32) {
+    #[cfg(target_arch = "arm")]
+    cortex_m::asm::delay(cycles);
+    #[cfg(not(target_arch = "arm"))]
+    let _ = cycles;
+}
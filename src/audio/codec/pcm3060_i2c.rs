@@ -1,12 +1,13 @@
 // Based on https://github.com/electro-smith/libDaisy/blob/master/src/dev/codec_pcm3060.cpp.
 
-use cortex_m::asm;
-
+use super::{CodecControl, delay};
+use crate::audio::SampleRate;
+use crate::audio::interface::Error;
 use crate::hal;
 use hal::gpio;
+use hal::hal::blocking::i2c::{Write, WriteRead};
 use hal::i2c;
 use hal::pac;
-use hal::prelude::*;
 use hal::time;
 
 const I2C_FS: time::Hertz = time::Hertz::from_raw(100_000);
@@ -17,8 +18,16 @@ pub type Pins = (
     gpio::gpiob::PB11<gpio::Alternate<4, gpio::OpenDrain>>, // I2C2 SDA (PCM3060)
 );
 
-pub struct Codec {
-    i2c2: hal::i2c::I2c<pac::I2C2>,
+pub struct Codec<I2C = i2c::I2c<pac::I2C2>> {
+    i2c2: I2C,
+    sample_rate: SampleRate,
+    // The reset restores register defaults, these keep track of settings to
+    // be written again when the codec gets restarted.
+    input_gain: Option<u8>,
+    output_attenuation: Option<u8>,
+    mute: bool,
+    deemphasis: bool,
+    power_save: u8,
 }
 
 impl Codec {
@@ -26,6 +35,7 @@ impl Codec {
         clocks: &hal::rcc::CoreClocks,
         i2c2_rec: hal::rcc::rec::I2c2,
         i2c2_pins: Pins,
+        sample_rate: SampleRate,
     ) -> Self {
         let i2c2 = i2c::I2cExt::i2c(
             unsafe { pac::Peripherals::steal().I2C2 },
@@ -34,41 +44,163 @@ impl Codec {
             i2c2_rec,
             clocks,
         );
-        Self { i2c2 }
+        Self::new(i2c2, sample_rate)
     }
+}
 
-    pub fn start(&mut self) {
-        let i2c2 = &mut self.i2c2;
+impl<I2C: Write + WriteRead> Codec<I2C> {
+    pub fn new(i2c2: I2C, sample_rate: SampleRate) -> Self {
+        Self {
+            i2c2,
+            sample_rate,
+            input_gain: None,
+            output_attenuation: None,
+            mute: false,
+            deemphasis: false,
+            power_save: 0,
+        }
+    }
 
+    /// Reset the codec and configure it, applying settings made so far.
+    pub fn start(&mut self) -> Result<(), Error> {
         // Go through configuration setup.
         for (register, mask, set) in REGISTER_CONFIG {
-            let mut buffer = [0];
-            i2c2.write_read(I2C_CODEC_ADDRESS, &[*register], &mut buffer)
-                .unwrap();
+            let value = if *set { *mask } else { 0 };
+            self.update_register(*register, *mask, value)?;
+        }
+
+        // The sampling rate is detected automatically, only the
+        // de-emphasis filter must be told about it.
+        let deemphasis_rate = match self.sample_rate {
+            SampleRate::Hz32000 => DMF_32KHZ,
+            SampleRate::Hz48000 => DMF_48KHZ,
+            _ => DMF_44KHZ,
+        };
+        self.update_register(DAC_CTRL3_REGISTER, DMF_MASK, deemphasis_rate)?;
+
+        if let Some(value) = self.input_gain {
+            self.write_input_gain(value)?;
+        }
+        if let Some(value) = self.output_attenuation {
+            self.write_output_attenuation(value)?;
+        }
+        if self.mute {
+            self.update_register(DAC_CTRL2_REGISTER, DAC_MUTE_MASK, DAC_MUTE_MASK)?;
+        }
+        if self.deemphasis {
+            self.update_register(DAC_CTRL3_REGISTER, DMC_MASK, DMC_MASK)?;
+        }
+        if self.power_save != 0 {
+            self.update_register(SYS_CTRL_REGISTER, PSV_MASK, self.power_save)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_input_gain(&mut self, value: u8) -> Result<(), Error> {
+        self.update_register(ADC_ATTENUATION_LEFT_REGISTER, 0xFF, value)?;
+        self.update_register(ADC_ATTENUATION_RIGHT_REGISTER, 0xFF, value)
+    }
+
+    fn write_output_attenuation(&mut self, value: u8) -> Result<(), Error> {
+        self.update_register(DAC_ATTENUATION_LEFT_REGISTER, 0xFF, value)?;
+        self.update_register(DAC_ATTENUATION_RIGHT_REGISTER, 0xFF, value)
+    }
+
+    /// Overwrite bits selected by the mask with the given value, keeping the
+    /// rest of the register as it is.
+    fn update_register(&mut self, register: u8, mask: u8, value: u8) -> Result<(), Error> {
+        let i2c2 = &mut self.i2c2;
+        let error = Error::I2c { register };
+
+        let mut buffer = [0];
+        i2c2.write_read(I2C_CODEC_ADDRESS, &[register], &mut buffer)
+            .map_err(|_| error)?;
+
+        let value = (buffer[0] & !mask) | (value & mask);
+
+        i2c2.write(I2C_CODEC_ADDRESS, &[register, value])
+            .map_err(|_| error)?;
+
+        // Wait ~10us.
+        delay(5_000);
+
+        Ok(())
+    }
+}
+
+impl<I2C: Write + WriteRead> CodecControl for Codec<I2C> {
+    fn set_input_gain(&mut self, gain_db: f32) -> Result<(), Error> {
+        let gain_db = gain_db.clamp(ADC_GAIN_MIN_DB, ADC_GAIN_MAX_DB);
+        let value = to_half_db_steps(ADC_GAIN_0DB as f32 + gain_db * 2.0);
+        self.input_gain = Some(value);
+        self.write_input_gain(value)
+    }
 
-            let value = if *set {
-                buffer[0] | mask
-            } else {
-                buffer[0] & !mask
-            };
+    fn set_output_attenuation(&mut self, attenuation_db: f32) -> Result<(), Error> {
+        let attenuation_db = attenuation_db.clamp(0.0, DAC_ATTENUATION_MAX_DB);
+        let value = to_half_db_steps(DAC_ATTENUATION_0DB as f32 - attenuation_db * 2.0);
+        self.output_attenuation = Some(value);
+        self.write_output_attenuation(value)
+    }
 
-            i2c2.write(I2C_CODEC_ADDRESS, &[*register, value]).unwrap();
+    fn set_mute(&mut self, mute: bool) -> Result<(), Error> {
+        self.mute = mute;
+        let value = if mute { DAC_MUTE_MASK } else { 0 };
+        self.update_register(DAC_CTRL2_REGISTER, DAC_MUTE_MASK, value)
+    }
 
-            // Wait ~10us.
-            asm::delay(5_000);
+    fn set_deemphasis(&mut self, enabled: bool) -> Result<(), Error> {
+        // De-emphasis is defined only for single speed sampling rates.
+        if self.sample_rate.is_double_speed() {
+            return Err(Error::Unsupported);
         }
+        self.deemphasis = enabled;
+        let value = if enabled { DMC_MASK } else { 0 };
+        self.update_register(DAC_CTRL3_REGISTER, DMC_MASK, value)
+    }
+
+    fn set_power_save(&mut self, adc: bool, dac: bool) -> Result<(), Error> {
+        self.power_save = if adc { ADC_PSV_MASK } else { 0 } | if dac { DAC_PSV_MASK } else { 0 };
+        self.update_register(SYS_CTRL_REGISTER, PSV_MASK, self.power_save)
     }
 }
 
+/// Round the register value, given in 0.5 dB steps.
+fn to_half_db_steps(value: f32) -> u8 {
+    (value + 0.5) as u8
+}
+
 const SYS_CTRL_REGISTER: u8 = 0x40;
+const DAC_ATTENUATION_LEFT_REGISTER: u8 = 0x41;
+const DAC_ATTENUATION_RIGHT_REGISTER: u8 = 0x42;
 const ADC_CTRL1_REGISTER: u8 = 0x48;
 const DAC_CTRL1_REGISTER: u8 = 0x43;
+const DAC_CTRL2_REGISTER: u8 = 0x44;
+const DAC_CTRL3_REGISTER: u8 = 0x45;
+const ADC_ATTENUATION_LEFT_REGISTER: u8 = 0x46;
+const ADC_ATTENUATION_RIGHT_REGISTER: u8 = 0x47;
 
 const MRST_MASK: u8 = 0x80;
 const SRST_MASK: u8 = 0x40;
 const ADC_PSV_MASK: u8 = 0x20;
 const DAC_PSV_MASK: u8 = 0x10;
+const PSV_MASK: u8 = ADC_PSV_MASK | DAC_PSV_MASK;
 const FMT_MASK: u8 = 0x1;
+const DMF_MASK: u8 = 0x60;
+const DMC_MASK: u8 = 0x10;
+const DAC_MUTE_MASK: u8 = 0x03;
+
+// Digital attenuation runs in 0.5 dB steps, lower values get muted.
+const DAC_ATTENUATION_0DB: u8 = 0xFF;
+const DAC_ATTENUATION_MAX_DB: f32 = 100.0;
+const ADC_GAIN_0DB: u8 = 0xD7;
+const ADC_GAIN_MIN_DB: f32 = -100.0;
+const ADC_GAIN_MAX_DB: f32 = 20.0;
+
+const DMF_44KHZ: u8 = 0x00;
+const DMF_48KHZ: u8 = 0x20;
+const DMF_32KHZ: u8 = 0x40;
 
 const REGISTER_CONFIG: &[(u8, u8, bool)] = &[
     // Reset Codec.
@@ -81,3 +213,101 @@ const REGISTER_CONFIG: &[(u8, u8, bool)] = &[
     (SYS_CTRL_REGISTER, ADC_PSV_MASK, false),
     (SYS_CTRL_REGISTER, DAC_PSV_MASK, false),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock::{MockI2c, Transaction};
+    use super::*;
+
+    #[test]
+    fn registers_are_read_modified_and_written() {
+        let mut i2c = MockI2c::new();
+        i2c.registers[SYS_CTRL_REGISTER as usize] = 0xF1;
+        let mut codec = Codec::new(i2c, SampleRate::Hz48000);
+        codec.start().unwrap();
+
+        assert_eq!(
+            codec.i2c2.transactions[..2],
+            [
+                Transaction::WriteRead(I2C_CODEC_ADDRESS, [0x40].to_vec()),
+                Transaction::Write(I2C_CODEC_ADDRESS, [0x40, 0x71].to_vec()),
+            ]
+        );
+        assert_eq!(codec.i2c2.writes().len(), REGISTER_CONFIG.len() + 1);
+        assert_eq!(codec.i2c2.registers[SYS_CTRL_REGISTER as usize], 0x01);
+        assert_eq!(codec.i2c2.registers[ADC_CTRL1_REGISTER as usize], 0x01);
+        assert_eq!(codec.i2c2.registers[DAC_CTRL1_REGISTER as usize], 0x01);
+    }
+
+    #[test]
+    fn deemphasis_rate_follows_sample_rate() {
+        let mut i2c = MockI2c::new();
+        i2c.registers[DAC_CTRL3_REGISTER as usize] = 0x80;
+        let mut codec = Codec::new(i2c, SampleRate::Hz32000);
+        codec.start().unwrap();
+        assert_eq!(codec.i2c2.registers[DAC_CTRL3_REGISTER as usize], 0xC0);
+
+        codec.set_deemphasis(true).unwrap();
+        assert_eq!(codec.i2c2.registers[DAC_CTRL3_REGISTER as usize], 0xD0);
+    }
+
+    #[test]
+    fn deemphasis_is_unsupported_in_double_speed() {
+        let mut codec = Codec::new(MockI2c::new(), SampleRate::Hz96000);
+        assert_eq!(codec.set_deemphasis(true), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn attenuation_is_set_in_half_db_steps() {
+        let mut codec = Codec::new(MockI2c::new(), SampleRate::Hz48000);
+        codec.set_output_attenuation(10.0).unwrap();
+        assert_eq!(codec.i2c2.registers[0x41], 0xEB);
+        assert_eq!(codec.i2c2.registers[0x42], 0xEB);
+
+        codec.set_input_gain(-0.5).unwrap();
+        assert_eq!(codec.i2c2.registers[0x46], 0xD6);
+        assert_eq!(codec.i2c2.registers[0x47], 0xD6);
+    }
+
+    #[test]
+    fn mute_keeps_other_bits() {
+        let mut i2c = MockI2c::new();
+        i2c.registers[DAC_CTRL2_REGISTER as usize] = 0x40;
+        let mut codec = Codec::new(i2c, SampleRate::Hz48000);
+        codec.set_mute(true).unwrap();
+        assert_eq!(codec.i2c2.registers[DAC_CTRL2_REGISTER as usize], 0x43);
+        codec.set_mute(false).unwrap();
+        assert_eq!(codec.i2c2.registers[DAC_CTRL2_REGISTER as usize], 0x40);
+    }
+
+    #[test]
+    fn settings_are_applied_again_on_restart() {
+        let mut codec = Codec::new(MockI2c::new(), SampleRate::Hz48000);
+        codec.start().unwrap();
+        codec.set_output_attenuation(10.0).unwrap();
+        codec.set_mute(true).unwrap();
+        codec.set_power_save(false, true).unwrap();
+
+        // Emulate the reset restoring register defaults.
+        codec.i2c2.registers = [0; 256];
+        codec.start().unwrap();
+        assert_eq!(codec.i2c2.registers[0x41], 0xEB);
+        assert_eq!(codec.i2c2.registers[0x42], 0xEB);
+        assert_eq!(codec.i2c2.registers[DAC_CTRL2_REGISTER as usize], 0x03);
+        assert_eq!(codec.i2c2.registers[SYS_CTRL_REGISTER as usize], 0x10);
+        assert_eq!(codec.i2c2.registers[0x46], 0x00);
+    }
+
+    #[test]
+    fn failing_register_is_reported() {
+        let mut i2c = MockI2c::new();
+        i2c.fail_after = Some(5);
+        let mut codec = Codec::new(i2c, SampleRate::Hz48000);
+        assert_eq!(
+            codec.start(),
+            Err(Error::I2c {
+                register: ADC_CTRL1_REGISTER
+            })
+        );
+    }
+}
@@ -1,5 +1,7 @@
 // Based on https://github.com/electro-smith/libDaisy/blob/ae9b45e2927aafba5f261f2ff36e3f41ae74d019/src/daisy_seed.cpp#L299.
 
+use super::CodecControl;
+use crate::audio::interface::Error;
 use crate::hal;
 use hal::gpio;
 
@@ -18,7 +20,20 @@ impl Codec {
         }
     }
 
-    pub fn start(&mut self) {
+    pub fn start(&mut self) -> Result<(), Error> {
         self.deemphasis.set_low();
+        Ok(())
+    }
+}
+
+// In parallel mode, PCM3060 offers only de-emphasis, fixed to 44.1 kHz.
+impl CodecControl for Codec {
+    fn set_deemphasis(&mut self, enabled: bool) -> Result<(), Error> {
+        if enabled {
+            self.deemphasis.set_high();
+        } else {
+            self.deemphasis.set_low();
+        }
+        Ok(())
     }
 }
@@ -1,11 +1,12 @@
 // Based on https://github.com/backtail/libdaisy-rust.
 
-use cortex_m::asm;
-use cortex_m::prelude::_embedded_hal_blocking_i2c_Write;
-
 use num_enum::IntoPrimitive;
 
+use super::{CodecControl, delay};
+use crate::audio::SampleRate;
+use crate::audio::interface::Error;
 use crate::hal;
+use hal::hal::blocking::i2c::Write;
 use hal::i2c;
 use hal::pac;
 use hal::time;
@@ -18,8 +19,45 @@ pub type Pins = (
     hal::gpio::gpiob::PB11<hal::gpio::Alternate<4, hal::gpio::OpenDrain>>, // I2C2 SDA (WM8731)
 );
 
-pub struct Codec {
-    i2c2: hal::i2c::I2c<pac::I2C2>,
+/// Headphone output of WM8731.
+#[derive(Debug, Clone, Copy)]
+pub struct Headphone {
+    /// Volume in 1 dB steps, 121 being 0 dB and 127 the maximum of +6 dB.
+    /// Values below 48 mute the output.
+    pub volume: u8,
+    /// Change the volume only when the signal crosses zero, avoiding clicks.
+    pub zero_cross: bool,
+}
+
+/// Attenuation of the microphone input routed to outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sidetone {
+    Db6,
+    Db9,
+    Db12,
+    Db15,
+}
+
+/// Configuration of the headphone output and analog routing of WM8731.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnalogOutput {
+    /// Enabled headphone output, muted when `None`.
+    pub headphone: Option<Headphone>,
+    /// Route line input directly to outputs, next to the DAC.
+    pub bypass: bool,
+    /// Route microphone input to outputs, next to the DAC.
+    pub sidetone: Option<Sidetone>,
+}
+
+pub struct Codec<I2C = i2c::I2c<pac::I2C2>> {
+    i2c2: I2C,
+    sample_rate: SampleRate,
+    analog_output: AnalogOutput,
+    // Registers are write-only, these keep track of their settings, so they
+    // can be combined and written again when the codec gets restarted.
+    line_input: u16,
+    digital_path: u8,
+    power_down: u8,
 }
 
 impl Codec {
@@ -27,6 +65,8 @@ impl Codec {
         clocks: &hal::rcc::CoreClocks,
         i2c2_rec: hal::rcc::rec::I2c2,
         i2c2_pins: Pins,
+        sample_rate: SampleRate,
+        analog_output: AnalogOutput,
     ) -> Self {
         let i2c2 = i2c::I2cExt::i2c(
             unsafe { pac::Peripherals::steal().I2C2 },
@@ -35,25 +75,187 @@ impl Codec {
             i2c2_rec,
             clocks,
         );
-        Self { i2c2 }
+        Self::new(i2c2, sample_rate, analog_output)
+    }
+}
+
+impl<I2C: Write> Codec<I2C> {
+    pub fn new(i2c2: I2C, sample_rate: SampleRate, analog_output: AnalogOutput) -> Self {
+        Self {
+            i2c2,
+            sample_rate,
+            analog_output,
+            line_input: LINE_INPUT_DEFAULT,
+            digital_path: DIGITAL_PATH_DEFAULT,
+            power_down: POWER_DOWN_DEFAULT,
+        }
     }
 
-    pub fn start(&mut self) {
-        let i2c2 = &mut self.i2c2;
+    /// Reset the codec and configure it, applying settings made so far.
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.power_down = self.analog_power_down(self.power_down);
 
         // Go through configuration setup.
-        for (register, value) in REGISTER_CONFIG {
-            let register: u8 = (*register).into();
-            let value: u8 = *value;
-            let byte1: u8 = ((register << 1) & 0b1111_1110) | ((value >> 7) & 0b0000_0001u8);
-            let byte2: u8 = value;
-            let bytes = [byte1, byte2];
+        for (register, value) in self.register_config() {
+            self.write_register(register, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reconfigure the headphone output and analog routing.
+    pub fn set_analog_output(&mut self, analog_output: AnalogOutput) -> Result<(), Error> {
+        self.analog_output = analog_output;
+
+        let headphone = self.headphone_volume();
+        self.write_register(Register::LOUT1V, headphone)?;
+        self.write_register(Register::ROUT1V, headphone)?;
+        self.write_register(Register::APANA, self.analog_path())?;
+
+        self.power_down = self.analog_power_down(self.power_down);
+        self.write_register(Register::PWR, self.power_down.into())
+    }
+
+    fn register_config(&self) -> [(Register, u16); 12] {
+        let sampling_rate = if self.sample_rate.is_double_speed() {
+            DOUBLE_SPEED_SAMPLING_RATE
+        } else {
+            SINGLE_SPEED_SAMPLING_RATE
+        };
+
+        [
+            // Reset Codec.
+            (Register::RESET, 0x00),
+            // Set line inputs, 0dB unless changed.
+            (Register::LINVOL, self.line_input),
+            (Register::RINVOL, self.line_input),
+            // Set headphone volume, muted unless enabled.
+            (Register::LOUT1V, self.headphone_volume()),
+            (Register::ROUT1V, self.headphone_volume()),
+            // Set analog and digital routing.
+            (Register::APANA, self.analog_path()),
+            (Register::APDIGI, self.digital_path.into()),
+            // Configure power management.
+            (Register::PWR, self.power_down.into()),
+            // Configure digital format.
+            (Register::IFACE, 0x09),
+            // Set samplerate.
+            (Register::SRATE, sampling_rate),
+            (Register::ACTIVE, 0x00),
+            (Register::ACTIVE, 0x01),
+        ]
+    }
+
+    fn headphone_volume(&self) -> u16 {
+        match self.analog_output.headphone {
+            Some(headphone) => {
+                let zero_cross = if headphone.zero_cross { ZCEN_MASK } else { 0 };
+                u16::from(headphone.volume.min(HEADPHONE_VOLUME_MAX)) | zero_cross
+            }
+            None => 0x00,
+        }
+    }
+
+    fn analog_path(&self) -> u16 {
+        let mut analog_path = ANALOG_PATH_DEFAULT;
+        if self.analog_output.bypass {
+            analog_path |= BYPASS_MASK;
+        }
+        if let Some(sidetone) = self.analog_output.sidetone {
+            let attenuation = match sidetone {
+                Sidetone::Db6 => 0b00,
+                Sidetone::Db9 => 0b01,
+                Sidetone::Db12 => 0b10,
+                Sidetone::Db15 => 0b11,
+            };
+            analog_path |= SIDETONE_MASK | (attenuation << SIDEATT_SHIFT);
+        }
+        analog_path
+    }
+
+    /// Power the microphone input only when it is used for sidetone.
+    fn analog_power_down(&self, power_down: u8) -> u8 {
+        if self.analog_output.sidetone.is_some() {
+            power_down & !MICPD_MASK
+        } else {
+            power_down | MICPD_MASK
+        }
+    }
+
+    /// Write a 9-bit value to the register.
+    fn write_register(&mut self, register: Register, value: u16) -> Result<(), Error> {
+        let register: u8 = register.into();
+        let byte1: u8 = ((register << 1) & 0b1111_1110) | ((value >> 8) as u8 & 0b0000_0001u8);
+        let byte2: u8 = value as u8;
+        let bytes = [byte1, byte2];
+
+        self.i2c2
+            .write(I2C_CODEC_ADDRESS, &bytes)
+            .map_err(|_| Error::I2c { register })?;
+
+        // Wait ~10us.
+        delay(5_000);
+
+        Ok(())
+    }
+
+    fn update_digital_path(&mut self, mask: u8, value: u8) -> Result<(), Error> {
+        self.digital_path = (self.digital_path & !mask) | (value & mask);
+        self.write_register(Register::APDIGI, self.digital_path.into())
+    }
+}
+
+// Line outputs of WM8731 have a fixed level, output attenuation is therefore
+// applied to the headphone output and supported only when it is enabled.
+impl<I2C: Write> CodecControl for Codec<I2C> {
+    fn set_input_gain(&mut self, gain_db: f32) -> Result<(), Error> {
+        let gain_db = gain_db.clamp(LINE_INPUT_MIN_DB, LINE_INPUT_MAX_DB);
+        self.line_input = ((gain_db - LINE_INPUT_MIN_DB) / LINE_INPUT_STEP_DB + 0.5) as u16;
+        self.write_register(Register::LINVOL, self.line_input)?;
+        self.write_register(Register::RINVOL, self.line_input)
+    }
+
+    fn set_output_attenuation(&mut self, attenuation_db: f32) -> Result<(), Error> {
+        let headphone = self
+            .analog_output
+            .headphone
+            .as_mut()
+            .ok_or(Error::Unsupported)?;
+        let attenuation_db = attenuation_db.clamp(0.0, HEADPHONE_ATTENUATION_MAX_DB);
+        headphone.volume = HEADPHONE_VOLUME_0DB - (attenuation_db + 0.5) as u8;
+
+        let headphone = self.headphone_volume();
+        self.write_register(Register::LOUT1V, headphone)?;
+        self.write_register(Register::ROUT1V, headphone)
+    }
+
+    fn set_mute(&mut self, mute: bool) -> Result<(), Error> {
+        let value = if mute { DACMU_MASK } else { 0 };
+        self.update_digital_path(DACMU_MASK, value)
+    }
 
-            i2c2.write(I2C_CODEC_ADDRESS, &bytes).unwrap_or_default();
+    fn set_deemphasis(&mut self, enabled: bool) -> Result<(), Error> {
+        let value = match (enabled, self.sample_rate) {
+            (false, _) => DEEMP_DISABLED,
+            (true, SampleRate::Hz32000) => DEEMP_32KHZ,
+            (true, SampleRate::Hz44100) => DEEMP_44KHZ,
+            (true, SampleRate::Hz48000) => DEEMP_48KHZ,
+            (true, _) => return Err(Error::Unsupported),
+        };
+        self.update_digital_path(DEEMP_MASK, value)
+    }
 
-            // Wait ~10us.
-            asm::delay(5_000);
+    fn set_power_save(&mut self, adc: bool, dac: bool) -> Result<(), Error> {
+        let adc_mask = LINEINPD_MASK | ADCPD_MASK;
+        let mut power_down = self.power_down & !(adc_mask | DACPD_MASK);
+        if adc {
+            power_down |= adc_mask;
+        }
+        if dac {
+            power_down |= DACPD_MASK;
         }
+        self.power_down = power_down;
+        self.write_register(Register::PWR, power_down.into())
     }
 }
 
@@ -74,29 +276,222 @@ enum Register {
     RESET = 0x0F,
 }
 
-#[cfg(not(feature = "sampling_rate_96khz"))]
-const SAMPLING_RATE: u8 = 0x00; // 48 kHz
-#[cfg(feature = "sampling_rate_96khz")]
-const SAMPLING_RATE: u8 = 0x07; // 96 kHz
-
-const REGISTER_CONFIG: &[(Register, u8)] = &[
-    // Reset Codec.
-    (Register::RESET, 0x00),
-    // Set line inputs 0dB.
-    (Register::LINVOL, 0x17),
-    (Register::RINVOL, 0x17),
-    // Set headphone to mute.
-    (Register::LOUT1V, 0x00),
-    (Register::ROUT1V, 0x00),
-    // Set analog and digital routing.
-    (Register::APANA, 0x12),
-    (Register::APDIGI, 0x01),
-    // Configure power management.
-    (Register::PWR, 0x42),
-    // Configure digital format.
-    (Register::IFACE, 0x09),
-    // Set samplerate.
-    (Register::SRATE, SAMPLING_RATE),
-    (Register::ACTIVE, 0x00),
-    (Register::ACTIVE, 0x01),
-];
+// Line input volume runs from -34.5 dB to +12 dB in 1.5 dB steps.
+const LINE_INPUT_DEFAULT: u16 = 0x17; // 0 dB
+const LINE_INPUT_MIN_DB: f32 = -34.5;
+const LINE_INPUT_MAX_DB: f32 = 12.0;
+const LINE_INPUT_STEP_DB: f32 = 1.5;
+
+// Headphone volume in 1 dB steps down to -73 dB, zero cross detection in
+// bit 7.
+const HEADPHONE_VOLUME_MAX: u8 = 0x7F;
+const HEADPHONE_VOLUME_0DB: u8 = 0x79;
+const HEADPHONE_ATTENUATION_MAX_DB: f32 = 73.0;
+const ZCEN_MASK: u16 = 0x80;
+
+// Analog audio path control, microphone muted and DAC selected.
+const ANALOG_PATH_DEFAULT: u16 = 0x12;
+const BYPASS_MASK: u16 = 0x08;
+const SIDETONE_MASK: u16 = 0x20;
+const SIDEATT_SHIFT: u16 = 6;
+
+// Digital audio path control, ADC high pass filter disabled by ADCHPD in bit 0.
+const DIGITAL_PATH_DEFAULT: u8 = 0x01;
+const DEEMP_MASK: u8 = 0x06;
+const DEEMP_DISABLED: u8 = 0x00;
+const DEEMP_32KHZ: u8 = 0x02;
+const DEEMP_44KHZ: u8 = 0x04;
+const DEEMP_48KHZ: u8 = 0x06;
+const DACMU_MASK: u8 = 0x08;
+
+// Power down control, microphone input and clock output powered down.
+const POWER_DOWN_DEFAULT: u8 = 0x42;
+const LINEINPD_MASK: u8 = 0x01;
+const MICPD_MASK: u8 = 0x02;
+const ADCPD_MASK: u8 = 0x04;
+const DACPD_MASK: u8 = 0x08;
+
+// Sampling control in normal mode, SR in bits [5:2] and CLKIDIV2 in bit 6.
+// MCLK runs at 256 × fs for all sampling rates. Single speed uses SR 0000
+// at 256 × fs, double speed SR 0111 at 128 × fs, reached by halving MCLK.
+const SR_SHIFT: u16 = 2;
+const CLKIDIV2_MASK: u16 = 0x40;
+const SINGLE_SPEED_SAMPLING_RATE: u16 = 0b0000 << SR_SHIFT; // 32, 44.1 and 48 kHz
+const DOUBLE_SPEED_SAMPLING_RATE: u16 = (0b0111 << SR_SHIFT) | CLKIDIV2_MASK; // 88.2 and 96 kHz
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock::{MockI2c, Transaction};
+    use super::*;
+
+    fn started(sample_rate: SampleRate, analog_output: AnalogOutput) -> Codec<MockI2c> {
+        let mut codec = Codec::new(MockI2c::new(), sample_rate, analog_output);
+        codec.start().unwrap();
+        codec
+    }
+
+    #[test]
+    fn start_writes_register_sequence() {
+        let codec = started(SampleRate::Hz48000, AnalogOutput::default());
+        assert!(
+            codec
+                .i2c2
+                .transactions
+                .iter()
+                .all(|t| matches!(t, Transaction::Write(I2C_CODEC_ADDRESS, _)))
+        );
+        assert_eq!(
+            codec.i2c2.writes(),
+            [
+                &[0x1E, 0x00][..],
+                &[0x00, 0x17],
+                &[0x02, 0x17],
+                &[0x04, 0x00],
+                &[0x06, 0x00],
+                &[0x08, 0x12],
+                &[0x0A, 0x01],
+                &[0x0C, 0x42],
+                &[0x0E, 0x09],
+                &[0x10, 0x00],
+                &[0x12, 0x00],
+                &[0x12, 0x01],
+            ]
+        );
+    }
+
+    #[test]
+    fn double_speed_is_configured() {
+        // SRATE is register 0x08, SR = 0111 in bits [5:2] with CLKIDIV2 in
+        // bit 6 gives 0x5C.
+        for sample_rate in [SampleRate::Hz88200, SampleRate::Hz96000] {
+            let codec = started(sample_rate, AnalogOutput::default());
+            assert_eq!(codec.i2c2.writes()[9], [0x10, 0x5C]);
+        }
+        for sample_rate in [SampleRate::Hz32000, SampleRate::Hz44100] {
+            let codec = started(sample_rate, AnalogOutput::default());
+            assert_eq!(codec.i2c2.writes()[9], [0x10, 0x00]);
+        }
+    }
+
+    #[test]
+    fn ninth_data_bit_is_packed_into_address_byte() {
+        let mut codec = Codec::new(MockI2c::new(), SampleRate::Hz48000, AnalogOutput::default());
+        codec.write_register(Register::LOUT1V, 0x1F9).unwrap();
+        assert_eq!(codec.i2c2.writes(), [&[0x05, 0xF9]]);
+    }
+
+    #[test]
+    fn headphone_and_sidetone_are_configured() {
+        let analog_output = AnalogOutput {
+            headphone: Some(Headphone {
+                volume: 0x79,
+                zero_cross: true,
+            }),
+            bypass: true,
+            sidetone: Some(Sidetone::Db12),
+        };
+        let codec = started(SampleRate::Hz48000, analog_output);
+        let writes = codec.i2c2.writes();
+        assert_eq!(writes[3], [0x04, 0xF9]);
+        assert_eq!(writes[4], [0x06, 0xF9]);
+        assert_eq!(writes[5], [0x08, 0xBA]);
+        assert_eq!(writes[7], [0x0C, 0x40]);
+    }
+
+    #[test]
+    fn output_attenuation_sets_headphone_volume() {
+        let analog_output = AnalogOutput {
+            headphone: Some(Headphone {
+                volume: 0x79,
+                zero_cross: false,
+            }),
+            ..AnalogOutput::default()
+        };
+        let mut codec = started(SampleRate::Hz48000, analog_output);
+        codec.i2c2.transactions.clear();
+        codec.set_output_attenuation(10.0).unwrap();
+        codec.set_output_attenuation(100.0).unwrap();
+        codec.set_output_attenuation(-6.0).unwrap();
+        assert_eq!(
+            codec.i2c2.writes(),
+            [
+                &[0x04, 0x6F][..],
+                &[0x06, 0x6F],
+                &[0x04, 0x30],
+                &[0x06, 0x30],
+                &[0x04, 0x79],
+                &[0x06, 0x79],
+            ]
+        );
+
+        codec.i2c2.transactions.clear();
+        codec.set_output_attenuation(10.0).unwrap();
+        codec.start().unwrap();
+        assert_eq!(codec.i2c2.writes()[5], [0x04, 0x6F]);
+    }
+
+    #[test]
+    fn output_attenuation_needs_headphone() {
+        let mut codec = started(SampleRate::Hz48000, AnalogOutput::default());
+        codec.i2c2.transactions.clear();
+        assert_eq!(codec.set_output_attenuation(10.0), Err(Error::Unsupported));
+        assert!(codec.i2c2.transactions.is_empty());
+    }
+
+    #[test]
+    fn input_gain_is_clamped_to_register_range() {
+        let mut codec = started(SampleRate::Hz48000, AnalogOutput::default());
+        codec.i2c2.transactions.clear();
+        codec.set_input_gain(0.0).unwrap();
+        codec.set_input_gain(100.0).unwrap();
+        codec.set_input_gain(-100.0).unwrap();
+        assert_eq!(
+            codec.i2c2.writes(),
+            [
+                &[0x00, 0x17][..],
+                &[0x02, 0x17],
+                &[0x00, 0x1F],
+                &[0x02, 0x1F],
+                &[0x00, 0x00],
+                &[0x02, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn mute_and_deemphasis_share_register() {
+        let mut codec = started(SampleRate::Hz44100, AnalogOutput::default());
+        codec.i2c2.transactions.clear();
+        codec.set_mute(true).unwrap();
+        codec.set_deemphasis(true).unwrap();
+        codec.set_mute(false).unwrap();
+        assert_eq!(
+            codec.i2c2.writes(),
+            [&[0x0A, 0x09][..], &[0x0A, 0x0D], &[0x0A, 0x05]]
+        );
+    }
+
+    #[test]
+    fn settings_are_applied_again_on_restart() {
+        let mut codec = started(SampleRate::Hz48000, AnalogOutput::default());
+        codec.set_input_gain(6.0).unwrap();
+        codec.set_mute(true).unwrap();
+        codec.set_power_save(true, false).unwrap();
+        codec.i2c2.transactions.clear();
+
+        codec.start().unwrap();
+        let writes = codec.i2c2.writes();
+        assert_eq!(writes[1], [0x00, 0x1B]);
+        assert_eq!(writes[2], [0x02, 0x1B]);
+        assert_eq!(writes[6], [0x0A, 0x09]);
+        assert_eq!(writes[7], [0x0C, 0x47]);
+    }
+
+    #[test]
+    fn failing_register_is_reported() {
+        let mut i2c = MockI2c::new();
+        i2c.fail_after = Some(3);
+        let mut codec = Codec::new(i2c, SampleRate::Hz48000, AnalogOutput::default());
+        assert_eq!(codec.start(), Err(Error::I2c { register: 0x02 }));
+    }
+}
@@ -0,0 +1,82 @@
+//! Conversion of samples exchanged with the codec.
+//!
+//! The codec transfers 24-bit two's complement samples, stored in the lower
+//! bits of 32-bit DMA words. These functions convert them from and to the
+//! integer formats offered by the audio interface. Conversion to floats is
+//! provided by [`sample`](super::sample), building on these.
+
+/// Convert audio data from u24 to a sign-extended 24-bit integer.
+#[inline(always)]
+pub fn u24_to_i32(y: u32) -> i32 {
+    ((y << 8) as i32) >> 8
+}
+
+/// Convert audio data from a 24-bit integer to u24.
+///
+/// Values outside of the 24-bit range are clipped.
+#[inline(always)]
+pub fn i32_to_u24(x: i32) -> u32 {
+    x.clamp(-0x0080_0000, 0x007F_FFFF) as u32
+}
+
+/// Convert audio data from u24 to Q1.31 fixed-point format (`I1F31`).
+#[inline(always)]
+pub fn u24_to_q31(y: u32) -> i32 {
+    (y << 8) as i32
+}
+
+/// Convert audio data from Q1.31 fixed-point format (`I1F31`) to u24.
+///
+/// The lowest 8 bits that do not fit into the 24-bit sample are truncated.
+#[inline(always)]
+pub fn q31_to_u24(x: i32) -> u32 {
+    (x >> 8) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTREMES: [u32; 5] = [
+        0x0080_0000,
+        0x00FF_FFFF,
+        0x0000_0000,
+        0x0000_0001,
+        0x007F_FFFF,
+    ];
+
+    #[test]
+    fn i32_round_trip() {
+        for y in EXTREMES {
+            assert_eq!(i32_to_u24(u24_to_i32(y)) & 0x00FF_FFFF, y);
+        }
+    }
+
+    #[test]
+    fn i32_is_sign_extended() {
+        assert_eq!(u24_to_i32(0x0080_0000), -8_388_608);
+        assert_eq!(u24_to_i32(0x00FF_FFFF), -1);
+        assert_eq!(u24_to_i32(0x0000_0000), 0);
+        assert_eq!(u24_to_i32(0x007F_FFFF), 8_388_607);
+    }
+
+    #[test]
+    fn i32_out_of_range_is_clipped() {
+        assert_eq!(u24_to_i32(i32_to_u24(i32::MAX)), 8_388_607);
+        assert_eq!(u24_to_i32(i32_to_u24(i32::MIN)), -8_388_608);
+    }
+
+    #[test]
+    fn q31_round_trip() {
+        for y in EXTREMES {
+            assert_eq!(q31_to_u24(u24_to_q31(y)) & 0x00FF_FFFF, y);
+        }
+    }
+
+    #[test]
+    fn q31_covers_full_range() {
+        assert_eq!(u24_to_q31(0x0080_0000), i32::MIN);
+        assert_eq!(u24_to_q31(0x007F_FFFF), i32::MAX - 0xFF);
+        assert_eq!(u24_to_q31(0x00FF_FFFF), -256);
+    }
+}
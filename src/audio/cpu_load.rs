@@ -0,0 +1,146 @@
+//! Measurement of the CPU load caused by audio processing.
+//!
+//! The load is the time spent processing a block relative to the period of
+//! the block, i.e. the time available until the next block arrives. A load
+//! close to or above 1.0 leads to audio overruns.
+
+use crate::hal;
+use hal::pac::DWT;
+use hal::time;
+
+/// Cutoff of the filter smoothing the average load.
+const SMOOTHING_CUTOFF_HZ: f32 = 1.0;
+
+/// Meter of the CPU load, reported as a fraction of the block period.
+///
+/// [`Interface`](super::Interface) feeds one internally, accessible through
+/// [`Interface::cpu_load`](super::Interface::cpu_load). A standalone meter
+/// can be used to measure only a part of the processing:
+///
+/// ```
+/// let mut load = audio::CpuLoad::new(ccdr.clocks.sys_ck(), audio.fs, audio::BLOCK_LENGTH);
+///
+/// audio.handle_interrupt_dma1_str1(|block| {
+///     load.start();
+///     process(block);
+///     load.end();
+/// });
+/// ```
+///
+/// The DWT cycle counter must be enabled, which is done by
+/// [`Interface::spawn`](super::Interface::spawn).
+#[derive(Debug, Clone)]
+pub struct CpuLoad {
+    block_period_cycles: f32,
+    smoothing: f32,
+    start: u32,
+    average: f32,
+    peak: f32,
+    initialized: bool,
+}
+
+impl CpuLoad {
+    /// Create a meter for blocks of `block_length` frames at sampling rate
+    /// `fs`, with the core running at `sys_ck`.
+    pub fn new(sys_ck: time::Hertz, fs: time::Hertz, block_length: usize) -> Self {
+        let blocks_per_second = fs.to_Hz() as f32 / block_length as f32;
+        Self {
+            block_period_cycles: sys_ck.to_Hz() as f32 / blocks_per_second,
+            smoothing: (SMOOTHING_CUTOFF_HZ / blocks_per_second).min(1.0),
+            start: 0,
+            average: 0.0,
+            peak: 0.0,
+            initialized: false,
+        }
+    }
+
+    /// Mark the start of block processing.
+    #[inline(always)]
+    pub fn start(&mut self) {
+        self.start = DWT::cycle_count();
+    }
+
+    /// Mark the end of block processing and update the load.
+    #[inline(always)]
+    pub fn end(&mut self) {
+        self.update(DWT::cycle_count().wrapping_sub(self.start));
+    }
+
+    /// Update the load with the number of cycles spent processing a block.
+    pub fn update(&mut self, cycles: u32) {
+        let load = cycles as f32 / self.block_period_cycles;
+
+        if self.initialized {
+            self.average += (load - self.average) * self.smoothing;
+        } else {
+            self.average = load;
+            self.initialized = true;
+        }
+        self.peak = self.peak.max(load);
+    }
+
+    /// Smoothed average load.
+    pub fn average(&self) -> f32 {
+        self.average
+    }
+
+    /// The highest load since the creation or the last reset.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    /// Forget the collected average and peak.
+    pub fn reset(&mut self) {
+        self.average = 0.0;
+        self.peak = 0.0;
+        self.initialized = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meter() -> CpuLoad {
+        // 480 MHz / (48 kHz / 32) = 320 000 cycles per block
+        CpuLoad::new(
+            time::Hertz::from_raw(480_000_000),
+            time::Hertz::from_raw(48_000),
+            32,
+        )
+    }
+
+    #[test]
+    fn first_update_sets_average() {
+        let mut load = meter();
+        load.update(160_000);
+        assert_eq!(load.average(), 0.5);
+        assert_eq!(load.peak(), 0.5);
+    }
+
+    #[test]
+    fn average_is_smoothed_and_peak_is_held() {
+        let mut load = meter();
+        load.update(32_000);
+        load.update(320_000);
+        assert!(load.average() > 0.1 && load.average() < 0.2);
+        assert_eq!(load.peak(), 1.0);
+
+        for _ in 0..100_000 {
+            load.update(64_000);
+        }
+        assert!((load.average() - 0.2).abs() < 0.001);
+        assert_eq!(load.peak(), 1.0);
+    }
+
+    #[test]
+    fn reset_clears_measurements() {
+        let mut load = meter();
+        load.update(320_000);
+        load.reset();
+        assert_eq!(load.average(), 0.0);
+        assert_eq!(load.peak(), 0.0);
+        load.update(32_000);
+        assert_eq!(load.average(), 0.1);
+    }
+}
@@ -0,0 +1,324 @@
+//! Built-in processing for testing of codecs, running without a user
+//! callback.
+//!
+//! [`Diagnostics`] generates a test signal on the output while measuring
+//! levels of the input. It is passed to
+//! [`Interface::handle_interrupt_diagnostics`](super::Interface::handle_interrupt_diagnostics):
+//!
+//! ```
+//! let mut diagnostics = audio::Diagnostics::new(audio.fs, audio::diagnostics::Signal::Passthrough);
+//!
+//! audio.handle_interrupt_diagnostics(&mut diagnostics).unwrap();
+//! let [left, right] = diagnostics.levels();
+//! ```
+
+use super::interface::Block;
+use crate::hal;
+use hal::time;
+
+const TAU: f32 = 2.0 * core::f32::consts::PI;
+
+/// Test signal written to the output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Signal {
+    /// Input copied to the output. Run by
+    /// [`Interface::handle_interrupt_diagnostics`](super::Interface::handle_interrupt_diagnostics),
+    /// raw samples are copied, untouched by dither and clipping.
+    #[default]
+    Passthrough,
+    /// Sine tone of the given frequency and level, for left and right channel
+    /// respectively.
+    Tone([Tone; 2]),
+    /// White noise of the given peak level on both channels, 1.0 being the
+    /// full scale.
+    Noise { level: f32 },
+}
+
+/// Sine tone generated by [`Signal::Tone`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// Frequency in Hz.
+    pub frequency: f32,
+    /// Peak level, 1.0 being the full scale.
+    pub level: f32,
+}
+
+/// Level of a channel measured by [`Diagnostics`], 1.0 being the full scale.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+/// Generator of test signals and meter of input levels.
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    fs: f32,
+    signal: Signal,
+    // Phase of tones, in turns.
+    phase: [f32; 2],
+    seed: u32,
+    peak: [f32; 2],
+    // Running mean of squares, updated by the mean of each block, so single
+    // precision holds up over long measurements.
+    mean_square: [f32; 2],
+    count: u32,
+}
+
+impl Diagnostics {
+    /// Create diagnostics generating `signal` at sampling rate `fs`.
+    pub fn new(fs: time::Hertz, signal: Signal) -> Self {
+        Self {
+            fs: fs.to_Hz() as f32,
+            signal,
+            phase: [0.0; 2],
+            seed: 0x1234_5678,
+            peak: [0.0; 2],
+            mean_square: [0.0; 2],
+            count: 0,
+        }
+    }
+
+    /// Test signal currently written to the output.
+    pub fn signal(&self) -> Signal {
+        self.signal
+    }
+
+    /// Switch to another test signal, restarting tones from zero phase.
+    pub fn set_signal(&mut self, signal: Signal) {
+        self.signal = signal;
+        self.phase = [0.0; 2];
+    }
+
+    /// Measure input levels of the block and replace it with the test signal.
+    pub fn process<const N: usize>(&mut self, block: &mut Block<N>) {
+        let mut sum_of_squares = [0.0; 2];
+        for &(left, right) in block.iter() {
+            sum_of_squares[0] += self.measure(0, left);
+            sum_of_squares[1] += self.measure(1, right);
+        }
+        self.count = self.count.saturating_add(N as u32);
+        let weight = N as f32 / self.count as f32;
+        for (mean_square, sum) in self.mean_square.iter_mut().zip(sum_of_squares) {
+            *mean_square += (sum / N as f32 - *mean_square) * weight;
+        }
+
+        match self.signal {
+            Signal::Passthrough => (),
+            Signal::Tone(tones) => {
+                for frame in block.iter_mut() {
+                    *frame = (self.tone(0, tones[0]), self.tone(1, tones[1]));
+                }
+            }
+            Signal::Noise { level } => {
+                for frame in block.iter_mut() {
+                    *frame = (self.noise() * level, self.noise() * level);
+                }
+            }
+        }
+    }
+
+    /// Levels of left and right input since the creation or the last reset.
+    pub fn levels(&self) -> [Level; 2] {
+        [0, 1].map(|channel| Level {
+            peak: self.peak[channel],
+            rms: sqrt(self.mean_square[channel]),
+        })
+    }
+
+    /// Forget measured levels.
+    pub fn reset_levels(&mut self) {
+        self.peak = [0.0; 2];
+        self.mean_square = [0.0; 2];
+        self.count = 0;
+    }
+
+    /// Update the peak level and return the square of the sample.
+    #[inline(always)]
+    fn measure(&mut self, channel: usize, x: f32) -> f32 {
+        self.peak[channel] = self.peak[channel].max(x.abs());
+        x * x
+    }
+
+    #[inline(always)]
+    fn tone(&mut self, channel: usize, tone: Tone) -> f32 {
+        let y = sin(self.phase[channel]) * tone.level;
+        // Keep the phase within [0.0, 1.0), negative frequencies included.
+        let phase = self.phase[channel] + tone.frequency / self.fs;
+        let phase = phase - phase as i32 as f32;
+        self.phase[channel] = if phase < 0.0 { phase + 1.0 } else { phase };
+        y
+    }
+
+    /// Uniformly distributed noise in range `[-1.0, 1.0)`, using xorshift.
+    #[inline(always)]
+    fn noise(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / 8_388_608.0 - 1.0 // 2^23
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new(time::Hertz::from_raw(48_000), Signal::default())
+    }
+}
+
+/// Sine of the phase given in turns within `[0.0, 1.0)`, using a Taylor
+/// series folded to the first quadrant.
+fn sin(phase: f32) -> f32 {
+    let (phase, sign) = if phase >= 0.5 {
+        (phase - 0.5, -1.0)
+    } else {
+        (phase, 1.0)
+    };
+    let phase = if phase > 0.25 { 0.5 - phase } else { phase };
+
+    let x = phase * TAU;
+    let x2 = x * x;
+    let y = x
+        * (1.0
+            - x2 / 6.0
+                * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0 * (1.0 - x2 / 110.0)))));
+    y * sign
+}
+
+/// Square root of a non-negative number, refined by Newton's method from an
+/// estimate halving the exponent.
+fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 0x1FC0_0000);
+    for _ in 0..3 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::audio::BLOCK_LENGTH;
+
+    type DefaultBlock = Block<BLOCK_LENGTH>;
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!((a - b).abs() < tolerance, "{a} is not close to {b}");
+    }
+
+    #[test]
+    fn sine_matches_reference_values() {
+        for i in 0..64 {
+            let phase = i as f32 / 64.0;
+            assert_close(sin(phase), (phase as f64 * TAU as f64).sin() as f32, 1e-5);
+        }
+    }
+
+    #[test]
+    fn square_root_matches_reference_values() {
+        for x in [0.0, 1e-6, 0.02, 0.5, 1.0, 2.0, 1000.0] {
+            assert_close(sqrt(x), (x as f64).sqrt() as f32, 1e-5 * x.max(1.0));
+        }
+    }
+
+    #[test]
+    fn passthrough_keeps_input_and_measures_it() {
+        let mut diagnostics = Diagnostics::default();
+        let mut block: DefaultBlock = [(0.5, -0.25); BLOCK_LENGTH];
+        diagnostics.process(&mut block);
+
+        assert_eq!(block, [(0.5, -0.25); BLOCK_LENGTH]);
+        let [left, right] = diagnostics.levels();
+        assert_close(left.peak, 0.5, 1e-6);
+        assert_close(left.rms, 0.5, 1e-5);
+        assert_close(right.peak, 0.25, 1e-6);
+        assert_close(right.rms, 0.25, 1e-5);
+    }
+
+    #[test]
+    fn tone_has_expected_levels() {
+        let tones = [
+            Tone {
+                frequency: 1000.0,
+                level: 1.0,
+            },
+            Tone {
+                frequency: 440.0,
+                level: 0.5,
+            },
+        ];
+        let mut generator = Diagnostics::new(time::Hertz::from_raw(48_000), Signal::Tone(tones));
+        let mut meter = Diagnostics::default();
+
+        // 0.2 s fits whole periods of both tones.
+        for _ in 0..300 {
+            let mut block: DefaultBlock = [(0.0, 0.0); BLOCK_LENGTH];
+            generator.process(&mut block);
+            meter.process(&mut block);
+        }
+
+        let [left, right] = meter.levels();
+        assert_close(left.peak, 1.0, 1e-4);
+        assert_close(left.rms, core::f32::consts::FRAC_1_SQRT_2, 1e-3);
+        assert_close(right.peak, 0.5, 1e-3);
+        assert_close(right.rms, 0.5 * core::f32::consts::FRAC_1_SQRT_2, 1e-3);
+    }
+
+    #[test]
+    fn negative_frequency_inverts_tone() {
+        let tone = |frequency| {
+            Signal::Tone(
+                [Tone {
+                    frequency,
+                    level: 1.0,
+                }; 2],
+            )
+        };
+        let mut positive = Diagnostics::new(time::Hertz::from_raw(48_000), tone(1000.0));
+        let mut negative = Diagnostics::new(time::Hertz::from_raw(48_000), tone(-1000.0));
+
+        for _ in 0..100 {
+            let mut expected: DefaultBlock = [(0.0, 0.0); BLOCK_LENGTH];
+            let mut block: DefaultBlock = [(0.0, 0.0); BLOCK_LENGTH];
+            positive.process(&mut expected);
+            negative.process(&mut block);
+            assert!(negative.phase.iter().all(|p| (0.0..1.0).contains(p)));
+            for (y, e) in block.iter().zip(expected) {
+                assert_close(y.0, -e.0, 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn noise_stays_within_level() {
+        let mut generator =
+            Diagnostics::new(time::Hertz::from_raw(48_000), Signal::Noise { level: 0.5 });
+        let mut meter = Diagnostics::default();
+
+        for _ in 0..100 {
+            let mut block: DefaultBlock = [(0.0, 0.0); BLOCK_LENGTH];
+            generator.process(&mut block);
+            assert!(block.iter().all(|(l, r)| l.abs() <= 0.5 && r.abs() <= 0.5));
+            meter.process(&mut block);
+        }
+
+        // Uniform noise has RMS of level / sqrt(3).
+        let [left, right] = meter.levels();
+        assert_close(left.rms, 0.5 / 3.0_f32.sqrt(), 0.01);
+        assert_close(right.rms, 0.5 / 3.0_f32.sqrt(), 0.01);
+    }
+
+    #[test]
+    fn reset_clears_levels() {
+        let mut diagnostics = Diagnostics::default();
+        let mut block: DefaultBlock = [(1.0, 1.0); BLOCK_LENGTH];
+        diagnostics.process(&mut block);
+        diagnostics.reset_levels();
+        assert_eq!(diagnostics.levels(), [Level::default(); 2]);
+    }
+}
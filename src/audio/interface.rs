@@ -1,34 +1,126 @@
-use core::num::Wrapping;
 use core::ptr;
 
-use super::codec::{Codec, Pins as CodecPins};
-use super::transfer::{Channel, Config as TransferConfig, Sai1Pins, State, Sync, Transfer};
-use super::{BLOCK_LENGTH, DMA_BUFFER_LENGTH, FS, HALF_DMA_BUFFER_LENGTH};
+#[cfg(feature = "sai2")]
+use super::Mode;
+#[cfg(feature = "seed_1_1")]
+use super::codec::AnalogOutput;
+use super::codec::{Codec, CodecControl, Pins as CodecPins};
+use super::convert;
+use super::cpu_load::CpuLoad;
+use super::diagnostics::{Diagnostics, Signal};
+#[cfg(feature = "sai2")]
+use super::sample::DataSize;
+use super::sample::{self, Encoder};
+use super::transfer::{Channel, Config as TransferConfig, Sai, Sai1Pins, State, Sync, Transfer};
+#[cfg(feature = "sai2")]
+use super::transfer::{Format, Sai2Pins};
+use super::{
+    BLOCK_LENGTH, Config, DmaCache, MAX_BLOCK_LENGTH, MAX_DMA_BUFFER_LENGTH, MIN_BLOCK_LENGTH,
+};
+use crate::clocks;
 use crate::hal;
-use hal::pac::{CPUID, CorePeripherals};
+use hal::pac::{self, CPUID, CorePeripherals, DWT};
 use hal::time;
 
+// A static cannot depend on the block length of the interface, so buffers
+// are sized for the longest supported block. Each SAI has the TX buffer
+// followed by the RX buffer of its interface, interfaces with shorter blocks
+// use only the beginning, which is the part an MPU region gets to cover.
+// Buffers of SAI2 are reserved only with the `sai2` feature.
+#[repr(C, align(8192))]
+struct DmaBuffers {
+    sai1: [u32; MAX_DMA_BUFFER_LENGTH * 2],
+    #[cfg(feature = "sai2")]
+    sai2: [u32; MAX_DMA_BUFFER_LENGTH * 2],
+}
+
+// MPU regions must be aligned to their size, which is at most the size of
+// buffers of a single SAI.
+const _: () = assert!(
+    core::mem::size_of::<[u32; MAX_DMA_BUFFER_LENGTH * 2]>() == core::mem::align_of::<DmaBuffers>(),
+    "DMA buffers of each SAI must fill their alignment"
+);
+
 #[unsafe(link_section = ".sram1_bss")]
-static mut TX_BUFFER: [u32; DMA_BUFFER_LENGTH] = [0; DMA_BUFFER_LENGTH];
-#[unsafe(link_section = ".sram1_bss")]
-static mut RX_BUFFER: [u32; DMA_BUFFER_LENGTH] = [0; DMA_BUFFER_LENGTH];
+static mut DMA_BUFFERS: DmaBuffers = DmaBuffers {
+    sai1: [0; MAX_DMA_BUFFER_LENGTH * 2],
+    #[cfg(feature = "sai2")]
+    sai2: [0; MAX_DMA_BUFFER_LENGTH * 2],
+};
 
 pub type Frame = (f32, f32);
-pub type Block = [Frame; BLOCK_LENGTH];
+pub type Block<const N: usize = BLOCK_LENGTH> = [Frame; N];
+
+/// Initial content of the output block passed to
+/// [`Interface::handle_interrupt_dma1_str1_io`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputBlock {
+    /// The output block is zeroed before every call of the callback.
+    #[default]
+    Zeroed,
+    /// The output block holds what the callback wrote into it during the
+    /// previous call.
+    Retained,
+}
 
-#[derive(Debug)]
+/// Statistics of audio processing, measured in CPU cycles.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// Number of blocks that were not processed before DMA started
+    /// transferring the following one.
+    pub overruns: u32,
+    /// The longest time spent processing a block.
+    pub max_callback_cycles: u32,
+    /// Time spent processing the last block.
+    pub last_callback_cycles: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
-    I2c,
+    /// Configuration of the codec failed while writing the given register.
+    I2c {
+        register: u8,
+    },
     Dma,
+    /// The codec does not support the requested setting.
+    Unsupported,
 }
 
-pub struct Interface {
+/// Audio interface processing blocks of `N` frames, each of `SLOTS` samples.
+///
+/// The block length must be an even number between [`MIN_BLOCK_LENGTH`] and
+/// [`MAX_BLOCK_LENGTH`]. It defaults to [`BLOCK_LENGTH`].
+///
+/// DMA buffers live in a static placed into RAM_D2, where DMA can reach
+/// them. A static cannot depend on the block length of the interface, so
+/// they are sized for [`MAX_BLOCK_LENGTH`] and take 8 KB per SAI whatever
+/// the block length is, 16 KB with the `sai2` feature. Shorter blocks use
+/// only the beginning of them.
+///
+/// [`MIN_BLOCK_LENGTH`]: super::MIN_BLOCK_LENGTH
+/// [`MAX_BLOCK_LENGTH`]: super::MAX_BLOCK_LENGTH
+/// [`BLOCK_LENGTH`]: super::BLOCK_LENGTH
+///
+/// The on-board codec is served through SAI1 by [`Interface::init`]. A second
+/// instance for an external codec on SAI2 can be created by
+/// [`Interface::init_sai2`], or by [`Interface::init_sai2_tdm`] for codecs
+/// exchanging `SLOTS` channels in TDM mode. TDM is available only on SAI2, as
+/// SAI1 is hardwired to the two-channel on-board codec.
+pub struct Interface<const N: usize = BLOCK_LENGTH, const SLOTS: usize = 2> {
     pub fs: time::Hertz,
-    codec: Codec,
+    codec: Option<Codec>,
     transfer: Transfer,
+    output_block: OutputBlock,
+    output: Block<N>,
+    encoder: Encoder,
+    #[cfg(feature = "sai2")]
+    data_size: DataSize,
+    stats: Stats,
+    cpu_load: CpuLoad,
+    dma_cache: DmaCache,
 }
 
-impl Interface {
+impl<const N: usize> Interface<N> {
     pub fn init(
         clocks: &hal::rcc::CoreClocks,
         sai1_rec: hal::rcc::rec::Sai1,
@@ -37,11 +129,23 @@ impl Interface {
         #[allow(unused_variables)] // i2c2 is not used on Seed 1.0
         i2c2_rec: hal::rcc::rec::I2c2,
         dma1_rec: hal::rcc::rec::Dma1,
-    ) -> Result<Interface, Error> {
+        config: Config,
+    ) -> Result<Self, Error> {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID_BLOCK_LENGTH;
+
         #[cfg(any(feature = "seed", feature = "seed_1_2"))]
         let codec = Codec::init(codec_pins);
-        #[cfg(any(feature = "seed_1_1", feature = "patch_sm"))]
-        let codec = Codec::init(clocks, i2c2_rec, codec_pins);
+        #[cfg(feature = "seed_1_1")]
+        let codec = Codec::init(
+            clocks,
+            i2c2_rec,
+            codec_pins,
+            config.sample_rate,
+            config.analog_output,
+        );
+        #[cfg(feature = "patch_sm")]
+        let codec = Codec::init(clocks, i2c2_rec, codec_pins, config.sample_rate);
 
         #[cfg(any(feature = "seed_1_2", feature = "seed"))]
         let transfer_config = TransferConfig {
@@ -49,6 +153,7 @@ impl Interface {
             rx_channel: Channel::B,
             tx_sync: Sync::Master,
             rx_sync: Sync::Slave,
+            mode: config.mode,
         };
         #[cfg(any(feature = "seed_1_1", feature = "patch_sm"))]
         let transfer_config = TransferConfig {
@@ -56,106 +161,869 @@ impl Interface {
             rx_channel: Channel::A,
             tx_sync: Sync::Slave,
             rx_sync: Sync::Master,
+            mode: config.mode,
         };
 
+        clocks::configure_audio_pll(config.sample_rate);
+
         let transfer = Transfer::init(
             clocks,
             sai1_rec,
             sai1_pins,
             dma1_rec,
-            unsafe { &mut *ptr::addr_of_mut!(TX_BUFFER) },
-            unsafe { &mut *ptr::addr_of_mut!(RX_BUFFER) },
+            unsafe { Self::tx_buffer(Sai::Sai1) },
+            unsafe { Self::rx_buffer(Sai::Sai1) },
             transfer_config,
         );
 
+        Ok(Self::new(clocks, Some(codec), transfer, config))
+    }
+
+    /// Initialize the audio interface on SAI2, serving an external codec
+    /// connected to pins 24 to 28 of the Seed.
+    ///
+    /// Block B of SAI2 receives input through SD B as the master of the bus,
+    /// block A sends output through SD A. DMA transfers run on streams 0 and
+    /// 1 of DMA2, raising the `DMA2_STR1` interrupt, from which the interrupt
+    /// handlers of this instance must be called.
+    ///
+    /// SAI2 runs on the same kernel clock as SAI1, PLL3 tuned by
+    /// [`Interface::init`], keeping both interfaces in sync. It must be
+    /// therefore initialized after the SAI1 interface, with the same sampling
+    /// rate in the configuration, before any of them gets spawned.
+    ///
+    /// Available with the `sai2` feature.
+    #[cfg(feature = "sai2")]
+    pub fn init_sai2(
+        clocks: &hal::rcc::CoreClocks,
+        sai2_rec: hal::rcc::rec::Sai2,
+        sai2_pins: Sai2Pins,
+        dma2_rec: hal::rcc::rec::Dma2,
+        config: Config,
+    ) -> Result<Self, Error> {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID_BLOCK_LENGTH;
+
+        if config.mode != Mode::Duplex {
+            return Err(Error::Unsupported);
+        }
+
+        let transfer = Transfer::init_sai2(
+            clocks,
+            sai2_rec,
+            sai2_pins,
+            dma2_rec,
+            unsafe { Self::tx_buffer(Sai::Sai2) },
+            unsafe { Self::rx_buffer(Sai::Sai2) },
+            Format::default(),
+        );
+
+        Ok(Self::new(clocks, None, transfer, config))
+    }
+
+    /// Process a block of stereo frames, to be called from the interrupt of
+    /// this interface, [`Self::interrupt`].
+    ///
+    /// This does not depend on the board nor on which DMA stream serves the
+    /// interface.
+    pub fn handle_interrupt(&mut self, callback: impl FnMut(&mut Block<N>)) -> Result<(), Error> {
+        self.handle_interrupt_dma1_str1(callback)
+    }
+
+    pub fn handle_interrupt_dma1_str1(
+        &mut self,
+        callback: impl FnMut(&mut Block<N>),
+    ) -> Result<(), Error> {
+        let encoder = &mut self.encoder;
+        Self::process_block(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            sample::i24_to_f32,
+            |x| encoder.encode(x),
+            callback,
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] running built-in
+    /// [`Diagnostics`] instead of a user callback, generating its test signal
+    /// and measuring input levels.
+    ///
+    /// With [`Signal::Passthrough`], received samples are copied to the
+    /// output as they are, bypassing dither and clipping of the float
+    /// conversion, so only the codec gets tested.
+    pub fn handle_interrupt_diagnostics(
+        &mut self,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(), Error> {
+        if diagnostics.signal() != Signal::Passthrough {
+            return self.handle_interrupt_dma1_str1(|block| diagnostics.process(block));
+        }
+
+        Self::process_dma_halves(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |rx_half, tx_half| {
+                let mut block: Block<N> = [(0.0, 0.0); N];
+                for (i, frame) in block.iter_mut().enumerate() {
+                    *frame = (
+                        sample::i24_to_f32(rx_half[i * 2]),
+                        sample::i24_to_f32(rx_half[i * 2 + 1]),
+                    );
+                }
+                diagnostics.process(&mut block);
+
+                // Transmitted frames start with the right channel, swap
+                // samples within each frame.
+                for (i, y) in tx_half.iter_mut().enumerate() {
+                    *y = rx_half[i ^ 1];
+                }
+            },
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] passing raw
+    /// sign-extended 24-bit integer samples, skipping float conversion.
+    ///
+    /// Values written outside of the 24-bit range are clipped.
+    pub fn handle_interrupt_dma1_str1_i32(
+        &mut self,
+        callback: impl FnMut(&mut [(i32, i32); N]),
+    ) -> Result<(), Error> {
+        Self::process_block(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            convert::u24_to_i32,
+            convert::i32_to_u24,
+            callback,
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] passing samples in
+    /// Q1.31 fixed-point format (`I1F31`), skipping float conversion.
+    ///
+    /// The lowest 8 bits of written samples are truncated.
+    pub fn handle_interrupt_dma1_str1_q31(
+        &mut self,
+        callback: impl FnMut(&mut [(i32, i32); N]),
+    ) -> Result<(), Error> {
+        Self::process_block(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            convert::u24_to_q31,
+            convert::q31_to_u24,
+            callback,
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] passing separate
+    /// input and output blocks to the callback.
+    ///
+    /// Whether the output block starts zeroed or keeps content from the
+    /// previous call is selected by [`Config::output_block`].
+    ///
+    /// [`Config::output_block`]: super::Config::output_block
+    pub fn handle_interrupt_dma1_str1_io(
+        &mut self,
+        mut callback: impl FnMut(&Block<N>, &mut Block<N>),
+    ) -> Result<(), Error> {
+        let output = &mut self.output;
+        let output_block = self.output_block;
+        let encoder = &mut self.encoder;
+
+        Self::process_dma_halves(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |rx_half, tx_half| {
+                let mut input: Block<N> = [(0.0, 0.0); N];
+
+                // Convert and copy received audio to input buffer.
+                for (i, frame) in input.iter_mut().enumerate() {
+                    *frame = (
+                        sample::i24_to_f32(rx_half[i * 2]),
+                        sample::i24_to_f32(rx_half[i * 2 + 1]),
+                    );
+                }
+
+                if output_block == OutputBlock::Zeroed {
+                    *output = [(0.0, 0.0); N];
+                }
+
+                // Invoke user-supplied callback.
+                callback(&input, output);
+
+                // Convert and copy output buffer to output audio buffer.
+                // Transmitted frames start with the right channel.
+                for (i, frame) in output.iter().enumerate() {
+                    let (left, right) = *frame;
+                    tx_half[i * 2] = encoder.encode(right);
+                    tx_half[i * 2 + 1] = encoder.encode(left);
+                }
+            },
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] for interfaces in
+    /// [`Mode::Output`], passing only an output block to the callback.
+    ///
+    /// Whether the output block starts zeroed or keeps content from the
+    /// previous call is selected by [`Config::output_block`].
+    ///
+    /// [`Config::output_block`]: super::Config::output_block
+    pub fn handle_interrupt_dma1_str1_output(
+        &mut self,
+        mut callback: impl FnMut(&mut Block<N>),
+    ) -> Result<(), Error> {
+        let output = &mut self.output;
+        let output_block = self.output_block;
+        let encoder = &mut self.encoder;
+
+        Self::process_dma_halves(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |_, tx_half| {
+                if output_block == OutputBlock::Zeroed {
+                    *output = [(0.0, 0.0); N];
+                }
+
+                // Invoke user-supplied callback.
+                callback(output);
+
+                // Convert and copy output buffer to output audio buffer.
+                // Transmitted frames start with the right channel.
+                for (i, frame) in output.iter().enumerate() {
+                    let (left, right) = *frame;
+                    tx_half[i * 2] = encoder.encode(right);
+                    tx_half[i * 2 + 1] = encoder.encode(left);
+                }
+            },
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] for interfaces in
+    /// [`Mode::Input`], passing only an input block to the callback.
+    pub fn handle_interrupt_dma1_str1_input(
+        &mut self,
+        mut callback: impl FnMut(&Block<N>),
+    ) -> Result<(), Error> {
+        Self::process_dma_halves(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |rx_half, _| {
+                let mut input: Block<N> = [(0.0, 0.0); N];
+
+                // Convert and copy received audio to input buffer.
+                for (i, frame) in input.iter_mut().enumerate() {
+                    *frame = (
+                        sample::i24_to_f32(rx_half[i * 2]),
+                        sample::i24_to_f32(rx_half[i * 2 + 1]),
+                    );
+                }
+
+                // Invoke user-supplied callback.
+                callback(&input);
+            },
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] passing
+    /// non-interleaved (planar) buffers to the callback.
+    ///
+    /// The callback receives left and right input, followed by left and right
+    /// output. Output buffers start zeroed on every call.
+    pub fn handle_interrupt_dma1_str1_planar(
+        &mut self,
+        mut callback: impl FnMut(&[f32], &[f32], &mut [f32], &mut [f32]),
+    ) -> Result<(), Error> {
+        let encoder = &mut self.encoder;
+
+        Self::process_dma_halves(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |rx_half, tx_half| {
+                let mut input_left = [0.0; N];
+                let mut input_right = [0.0; N];
+                let mut output_left = [0.0; N];
+                let mut output_right = [0.0; N];
+
+                // Convert and deinterleave received audio to callback buffers.
+                for i in 0..N {
+                    input_left[i] = sample::i24_to_f32(rx_half[i * 2]);
+                    input_right[i] = sample::i24_to_f32(rx_half[i * 2 + 1]);
+                }
+
+                // Invoke user-supplied callback.
+                callback(
+                    &input_left,
+                    &input_right,
+                    &mut output_left,
+                    &mut output_right,
+                );
+
+                // Convert and interleave callback buffers to output audio buffer.
+                // Transmitted frames start with the right channel.
+                for i in 0..N {
+                    tx_half[i * 2] = encoder.encode(output_right[i]);
+                    tx_half[i * 2 + 1] = encoder.encode(output_left[i]);
+                }
+            },
+        )
+    }
+
+    /// Alternative to [`Self::handle_interrupt_dma1_str1`] passing frames of
+    /// four channels, the first two served by this interface, the other two
+    /// by the `secondary` one started by [`Self::restart_with`].
+    #[cfg(feature = "patch")]
+    pub(super) fn handle_interrupt_with(
+        &mut self,
+        secondary: &mut Self,
+        mut callback: impl FnMut(&mut [[f32; 4]; N]),
+    ) -> Result<(), Error> {
+        let encoder = &mut self.encoder;
+
+        Self::process_dma_halves_with(
+            &mut self.transfer,
+            Some(&mut secondary.transfer),
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |(rx_half, tx_half), secondary_halves| {
+                let (secondary_rx_half, secondary_tx_half) =
+                    secondary_halves.expect("Secondary transfer is always passed");
+                let mut block = [[0.0; 4]; N];
+
+                // Convert and copy received audio of both interfaces to
+                // callback buffer.
+                for (i, frame) in block.iter_mut().enumerate() {
+                    *frame = [
+                        sample::i24_to_f32(rx_half[i * 2]),
+                        sample::i24_to_f32(rx_half[i * 2 + 1]),
+                        sample::i24_to_f32(secondary_rx_half[i * 2]),
+                        sample::i24_to_f32(secondary_rx_half[i * 2 + 1]),
+                    ];
+                }
+
+                // Invoke user-supplied callback.
+                callback(&mut block);
+
+                // Convert and copy callback buffer to output audio buffers.
+                // Transmitted frames start with the right channel.
+                for (i, frame) in block.iter().enumerate() {
+                    tx_half[i * 2] = encoder.encode(frame[1]);
+                    tx_half[i * 2 + 1] = encoder.encode(frame[0]);
+                    secondary_tx_half[i * 2] = encoder.encode(frame[3]);
+                    secondary_tx_half[i * 2 + 1] = encoder.encode(frame[2]);
+                }
+            },
+        )
+    }
+
+    /// Convert received audio into a block of interleaved frames, pass it to
+    /// the callback and convert it back to the output audio buffer.
+    fn process_block<S: Copy + Default>(
+        transfer: &mut Transfer,
+        stats: &mut Stats,
+        cpu_load: &mut CpuLoad,
+        dma_cache: DmaCache,
+        decode: impl Fn(u32) -> S,
+        mut encode: impl FnMut(S) -> u32,
+        mut callback: impl FnMut(&mut [(S, S); N]),
+    ) -> Result<(), Error> {
+        Self::process_dma_halves(transfer, stats, cpu_load, dma_cache, |rx_half, tx_half| {
+            let mut block = [(S::default(), S::default()); N];
+
+            // Convert and copy received audio to callback buffer.
+            for (i, frame) in block.iter_mut().enumerate() {
+                *frame = (decode(rx_half[i * 2]), decode(rx_half[i * 2 + 1]));
+            }
+
+            // Invoke user-supplied callback.
+            callback(&mut block);
+
+            // Convert and copy callback buffer to output audio buffer.
+            // Transmitted frames start with the right channel.
+            for (i, frame) in block.iter().enumerate() {
+                let (left, right) = *frame;
+                tx_half[i * 2] = encode(right);
+                tx_half[i * 2 + 1] = encode(left);
+            }
+        })
+    }
+}
+
+impl<const N: usize, const SLOTS: usize> Interface<N, SLOTS> {
+    const HALF_DMA_BUFFER_LENGTH: usize = N * SLOTS;
+    const DMA_BUFFER_LENGTH: usize = Self::HALF_DMA_BUFFER_LENGTH * 2; // 2 half-blocks
+
+    // Even length keeps the used part of DMA buffers aligned with cache lines.
+    const VALID_BLOCK_LENGTH: () = assert!(
+        N >= MIN_BLOCK_LENGTH && N <= MAX_BLOCK_LENGTH && N.is_multiple_of(2),
+        "block length must be an even number between 4 and 256"
+    );
+
+    // Buffers of SAI2 are shared by all slot counts, fitting 256 frames of
+    // two slots.
+    #[cfg(feature = "sai2")]
+    const VALID_TDM_SLOTS: () = assert!(
+        (SLOTS == 4 || SLOTS == 8) && N * SLOTS <= MAX_BLOCK_LENGTH * 2,
+        "TDM supports 4 or 8 slots, with up to 128 or 64 frames per block respectively"
+    );
+
+    /// Initialize the audio interface on SAI2 in TDM mode, exchanging
+    /// `SLOTS` channels with a multichannel codec, such as PCM3168 or
+    /// CS42448, connected to pins 24 to 28 of the Seed.
+    ///
+    /// Supported are 4 or 8 slots, each carrying a sample of the given size.
+    /// Slots of 24 and 32-bit samples are 32 bits long, slots of 16-bit
+    /// samples 16 bits. Frame sync is a single bit clock long pulse,
+    /// starting one bit before the first slot. Serial data and clocks are
+    /// mapped as in [`Interface::init_sai2`], to which the rest of its
+    /// documentation applies too.
+    ///
+    /// TDM is supported only on SAI2, SAI1 is hardwired to the two-channel
+    /// on-board codec.
+    ///
+    /// Blocks are processed by [`Self::handle_interrupt_dma2_str1`].
+    ///
+    /// Available with the `sai2` feature.
+    #[cfg(feature = "sai2")]
+    pub fn init_sai2_tdm(
+        clocks: &hal::rcc::CoreClocks,
+        sai2_rec: hal::rcc::rec::Sai2,
+        sai2_pins: Sai2Pins,
+        dma2_rec: hal::rcc::rec::Dma2,
+        data_size: DataSize,
+        config: Config,
+    ) -> Result<Self, Error> {
+        #[allow(clippy::let_unit_value)]
+        let _ = (Self::VALID_BLOCK_LENGTH, Self::VALID_TDM_SLOTS);
+
+        if config.mode != Mode::Duplex {
+            return Err(Error::Unsupported);
+        }
+
+        let transfer = Transfer::init_sai2(
+            clocks,
+            sai2_rec,
+            sai2_pins,
+            dma2_rec,
+            unsafe { Self::tx_buffer(Sai::Sai2) },
+            unsafe { Self::rx_buffer(Sai::Sai2) },
+            Format {
+                slots: SLOTS as u8,
+                data_size,
+            },
+        );
+
+        let mut interface = Self::new(clocks, None, transfer, config);
+        interface.data_size = data_size;
+        Ok(interface)
+    }
+
+    fn new(
+        clocks: &hal::rcc::CoreClocks,
+        codec: Option<Codec>,
+        transfer: Transfer,
+        config: Config,
+    ) -> Self {
         // Verifying safety requirements and recommendations of
         // invalidate_dcache_by_slice and clean_dcache_by_slice.
-        validate_slice_against_cache_line(unsafe { &*ptr::addr_of!(TX_BUFFER) });
-        validate_slice_against_cache_line(unsafe { &*ptr::addr_of!(RX_BUFFER) });
+        validate_slice_against_cache_line(unsafe { Self::tx_buffer(transfer.sai()) });
+        validate_slice_against_cache_line(unsafe { Self::rx_buffer(transfer.sai()) });
 
-        Ok(Self {
-            fs: FS,
+        if config.dma_cache == DmaCache::NonCacheable {
+            configure_non_cacheable_dma_buffers(transfer.sai(), Self::DMA_BUFFER_LENGTH * 2);
+        }
+
+        Self {
+            fs: config.sample_rate.frequency(),
             codec,
             transfer,
-        })
+            output_block: config.output_block,
+            output: [(0.0, 0.0); N],
+            encoder: Encoder::new(config.dither, config.clipping),
+            #[cfg(feature = "sai2")]
+            data_size: DataSize::Bits24,
+            stats: Stats::default(),
+            cpu_load: CpuLoad::new(clocks.sys_ck(), config.sample_rate.frequency(), N),
+            dma_cache: config.dma_cache,
+        }
     }
 
     /// Start audio streaming.
     pub fn spawn(mut self) -> Result<Self, Error> {
-        self.codec.start();
-        self.transfer.start();
+        enable_cycle_counter();
+        self.restart()?;
         Ok(self)
     }
 
-    pub fn handle_interrupt_dma1_str1(
+    /// Stop audio streaming.
+    ///
+    /// SAI and its DMA streams get disabled and rewound to the beginning of
+    /// audio buffers. The output buffer is cleared, so no stale samples are
+    /// played after the restart. Settings of the codec changed while stopped
+    /// are applied once the streaming gets resumed by [`Interface::restart`].
+    pub fn stop(&mut self) {
+        self.transfer.stop();
+
+        // Safety: DMA is stopped and the buffers are not accessed by
+        // anything else.
+        let tx_buffer = unsafe { Self::tx_buffer(self.transfer.sai()) };
+        tx_buffer.fill(0);
+        unsafe {
+            CorePeripherals::steal()
+                .SCB
+                .clean_dcache_by_slice(tx_buffer);
+        }
+
+        self.output = [(0.0, 0.0); N];
+    }
+
+    /// Start audio streaming again after [`Interface::stop`], going through
+    /// the codec start sequence first. The codec gets reset by it, settings
+    /// made through [`Interface::codec`] are then applied again.
+    pub fn restart(&mut self) -> Result<(), Error> {
+        if let Some(codec) = &mut self.codec {
+            codec.start()?;
+        }
+        self.transfer.start();
+        Ok(())
+    }
+
+    /// Start audio streaming of this and the `secondary` interface together,
+    /// running them in lock-step, so both can be served from the interrupt
+    /// of this one by [`Self::handle_interrupt_with`].
+    #[cfg(feature = "patch")]
+    pub(super) fn restart_with(&mut self, secondary: &mut Self) -> Result<(), Error> {
+        enable_cycle_counter();
+        for codec in [&mut self.codec, &mut secondary.codec]
+            .into_iter()
+            .flatten()
+        {
+            codec.start()?;
+        }
+        self.transfer.start_with(&mut secondary.transfer);
+        Ok(())
+    }
+
+    /// Interrupt raised whenever a block is ready for processing. It is
+    /// [`INTERRUPT`](super::INTERRUPT) for the on-board codec and
+    /// `SAI2_INTERRUPT` for interfaces on SAI2.
+    pub fn interrupt(&self) -> pac::Interrupt {
+        self.transfer.interrupt()
+    }
+
+    /// Statistics of audio processing since the start or the last reset.
+    ///
+    /// Callback cycles cover the whole processing of a block, including
+    /// sample conversion.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Reset collected statistics of audio processing, including the CPU
+    /// load.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+        self.cpu_load.reset();
+    }
+
+    /// Load of the CPU caused by audio processing, as a fraction of the block
+    /// period.
+    pub fn cpu_load(&self) -> &CpuLoad {
+        &self.cpu_load
+    }
+
+    /// Control of the codec, allowing to adjust its settings at runtime.
+    ///
+    /// Returns `None` on the SAI2 interface, which has no codec to control.
+    pub fn codec(&mut self) -> Option<&mut impl CodecControl> {
+        self.codec.as_mut()
+    }
+
+    /// Reconfigure the headphone output and analog routing of the WM8731
+    /// codec. The configuration is kept across [`Interface::restart`].
+    #[cfg(feature = "seed_1_1")]
+    pub fn set_analog_output(&mut self, analog_output: AnalogOutput) -> Result<(), Error> {
+        self.codec
+            .as_mut()
+            .ok_or(Error::Unsupported)?
+            .set_analog_output(analog_output)
+    }
+
+    /// Return whether any float output sample got clipped since the last
+    /// call. This is only tracked with [`sample::Clipping::Saturate`].
+    pub fn take_clipped(&mut self) -> bool {
+        self.encoder.take_clipped()
+    }
+
+    /// Process a block of frames holding a sample of each of `SLOTS` slots.
+    ///
+    /// This is meant for interfaces on SAI2, to be called from the
+    /// `DMA2_STR1` interrupt. Samples are converted from and to the data
+    /// size the interface was initialized with, slots are passed in order.
+    #[cfg(feature = "sai2")]
+    pub fn handle_interrupt_dma2_str1(
         &mut self,
-        mut callback: impl FnMut(&mut [(f32, f32); BLOCK_LENGTH]),
+        mut callback: impl FnMut(&mut [[f32; SLOTS]; N]),
+    ) -> Result<(), Error> {
+        let encoder = &mut self.encoder;
+        let data_size = self.data_size;
+
+        Self::process_dma_halves(
+            &mut self.transfer,
+            &mut self.stats,
+            &mut self.cpu_load,
+            self.dma_cache,
+            |rx_half, tx_half| {
+                let mut block = [[0.0; SLOTS]; N];
+
+                // Convert and copy received audio to callback buffer.
+                for (frame, rx_frame) in block.iter_mut().zip(rx_half.chunks(SLOTS)) {
+                    for (sample, &y) in frame.iter_mut().zip(rx_frame) {
+                        *sample = data_size.to_f32(y);
+                    }
+                }
+
+                // Invoke user-supplied callback.
+                callback(&mut block);
+
+                // Convert and copy callback buffer to output audio buffer.
+                for (frame, tx_frame) in block.iter().zip(tx_half.chunks_mut(SLOTS)) {
+                    for (&x, y) in frame.iter().zip(tx_frame) {
+                        *y = encoder.encode_as(x, data_size);
+                    }
+                }
+            },
+        )
+    }
+
+    /// Acknowledge the DMA interrupt and pass halves of RX and TX buffers that
+    /// are not currently used by DMA to the given function.
+    ///
+    /// Both halves hold `N` interleaved frames of raw 24-bit samples.
+    ///
+    /// Processing time is recorded in stats and the CPU load. If DMA finished
+    /// transferring the other half before the processing was done, an overrun
+    /// is counted.
+    fn process_dma_halves(
+        transfer: &mut Transfer,
+        stats: &mut Stats,
+        cpu_load: &mut CpuLoad,
+        dma_cache: DmaCache,
+        f: impl FnOnce(&[u32], &mut [u32]),
     ) -> Result<(), Error> {
-        let skip = match self.transfer.examine_interrupt() {
-            Ok(State::HalfSent) => (0, HALF_DMA_BUFFER_LENGTH),
-            Ok(State::FullSent) => (HALF_DMA_BUFFER_LENGTH, 0),
+        Self::process_dma_halves_with(
+            transfer,
+            None,
+            stats,
+            cpu_load,
+            dma_cache,
+            |(rx_half, tx_half), _| f(rx_half, tx_half),
+        )
+    }
+
+    /// Variant of [`Self::process_dma_halves`] passing also the same halves of
+    /// the `secondary` transfer running in lock-step, whose interrupt flags
+    /// get cleared.
+    #[allow(clippy::type_complexity)]
+    fn process_dma_halves_with(
+        transfer: &mut Transfer,
+        secondary: Option<&mut Transfer>,
+        stats: &mut Stats,
+        cpu_load: &mut CpuLoad,
+        dma_cache: DmaCache,
+        f: impl FnOnce((&[u32], &mut [u32]), Option<(&[u32], &mut [u32])>),
+    ) -> Result<(), Error> {
+        let skip = match transfer.examine_interrupt() {
+            Ok(State::HalfSent) => (0, Self::HALF_DMA_BUFFER_LENGTH),
+            Ok(State::FullSent) => (Self::HALF_DMA_BUFFER_LENGTH, 0),
             _ => return Err(Error::Dma),
         };
 
-        let mut block: Block = [(0.0, 0.0); BLOCK_LENGTH];
+        // Safety: Buffers are accessed only through this function, without any
+        // concurrency. The init function verifies that they have the correct
+        // size and alignment.
+        let buffers = |sai| unsafe { (Self::rx_buffer(sai), Self::tx_buffer(sai)) };
+        let (rx_buffer, tx_buffer) = buffers(transfer.sai());
+        let mut secondary_buffers = secondary.map(|secondary| {
+            // The secondary transfer is in the same state, its interrupt is
+            // only acknowledged.
+            let _ = secondary.examine_interrupt();
+            buffers(secondary.sai())
+        });
 
         // Force dcache to get populated from memory.
-        // Safety: RX buffer is accessed only through this function, without any
-        // concurrency. The init function verifies that the buffer has the
-        // correct size and alignment.
-        unsafe {
-            CorePeripherals::steal()
-                .SCB
-                .invalidate_dcache_by_slice(&mut *ptr::addr_of_mut!(RX_BUFFER));
+        let mut scb = unsafe { CorePeripherals::steal().SCB };
+        if dma_cache == DmaCache::Maintained {
+            unsafe {
+                scb.invalidate_dcache_by_slice(rx_buffer);
+                if let Some((rx_buffer, _)) = &mut secondary_buffers {
+                    scb.invalidate_dcache_by_slice(rx_buffer);
+                }
+            }
         }
 
-        // Convert and copy received audio to callback buffer.
-        let mut dma_index: usize = 0;
-        let mut block_index: usize = 0;
-        while dma_index < HALF_DMA_BUFFER_LENGTH {
-            let rx0: usize = dma_index + skip.1;
-            let rx1: usize = rx0 + 1;
+        let start = DWT::cycle_count();
 
-            let rx_y0 = unsafe { RX_BUFFER[rx0] };
-            let rx_y1 = unsafe { RX_BUFFER[rx1] };
+        f(
+            Self::halves(skip, rx_buffer, tx_buffer),
+            secondary_buffers
+                .as_mut()
+                .map(|(rx_buffer, tx_buffer)| Self::halves(skip, rx_buffer, tx_buffer)),
+        );
 
-            let y0 = u24_to_f32(rx_y0);
-            let y1 = u24_to_f32(rx_y1);
-            block[block_index] = (y0, y1);
+        // Force dcache to get flushed into memory.
+        if dma_cache == DmaCache::Maintained {
+            scb.clean_dcache_by_slice(tx_buffer);
+            if let Some((_, tx_buffer)) = &secondary_buffers {
+                scb.clean_dcache_by_slice(tx_buffer);
+            }
+        }
 
-            dma_index += 2;
-            block_index += 1;
+        let cycles = DWT::cycle_count().wrapping_sub(start);
+        stats.last_callback_cycles = cycles;
+        stats.max_callback_cycles = stats.max_callback_cycles.max(cycles);
+        cpu_load.update(cycles);
+        if transfer.is_interrupt_pending() {
+            stats.overruns = stats.overruns.wrapping_add(1);
         }
 
-        // Invoke user-supplied callback.
-        callback(&mut block);
+        Ok(())
+    }
 
-        // Convert and copy callback buffer to output audio buffer.
-        let mut dma_index: usize = 0;
-        let mut block_index: usize = 0;
-        while dma_index < HALF_DMA_BUFFER_LENGTH {
-            let tx0: usize = dma_index + skip.0;
-            let tx1: usize = tx0 + 1;
+    /// Halves of RX and TX buffers that are not currently used by DMA, at
+    /// offsets given by `skip`.
+    fn halves<'a>(
+        skip: (usize, usize),
+        rx_buffer: &'a [u32],
+        tx_buffer: &'a mut [u32],
+    ) -> (&'a [u32], &'a mut [u32]) {
+        (
+            &rx_buffer[skip.1..skip.1 + Self::HALF_DMA_BUFFER_LENGTH],
+            &mut tx_buffer[skip.0..skip.0 + Self::HALF_DMA_BUFFER_LENGTH],
+        )
+    }
 
-            let (y1, y0) = block[block_index];
-            unsafe { TX_BUFFER[tx0] = f32_to_u24(y0) };
-            unsafe { TX_BUFFER[tx1] = f32_to_u24(y1) };
+    /// TX buffer of the given SAI used by an interface of this block length.
+    ///
+    /// # Safety
+    ///
+    /// There must be only a single audio interface accessing the buffer.
+    unsafe fn tx_buffer(sai: Sai) -> &'static mut [u32] {
+        unsafe { &mut (&mut *dma_buffers(sai))[..Self::DMA_BUFFER_LENGTH] }
+    }
 
-            dma_index += 2;
-            block_index += 1;
+    /// RX buffer of the given SAI used by an interface of this block length,
+    /// following right after its TX buffer.
+    ///
+    /// # Safety
+    ///
+    /// There must be only a single audio interface accessing the buffer.
+    unsafe fn rx_buffer(sai: Sai) -> &'static mut [u32] {
+        unsafe {
+            &mut (&mut *dma_buffers(sai))[Self::DMA_BUFFER_LENGTH..Self::DMA_BUFFER_LENGTH * 2]
         }
+    }
+}
 
-        // Force dcache to get flushed into memory.
-        // Safety: TX buffer is accessed only through this function, without any
-        // concurrency. The init function verifies that the buffer has the
-        // correct size and alignment.
-        unsafe {
-            CorePeripherals::steal()
-                .SCB
-                .clean_dcache_by_slice(&*ptr::addr_of!(TX_BUFFER));
+/// DMA buffers reserved for the given SAI.
+fn dma_buffers(sai: Sai) -> *mut [u32; MAX_DMA_BUFFER_LENGTH * 2] {
+    // Safety: Only the address is taken, the buffers are not accessed.
+    unsafe {
+        match sai {
+            Sai::Sai1 => ptr::addr_of_mut!(DMA_BUFFERS.sai1),
+            #[cfg(feature = "sai2")]
+            Sai::Sai2 => ptr::addr_of_mut!(DMA_BUFFERS.sai2),
         }
+    }
+}
 
-        Ok(())
+/// Enable the cycle counter used to measure processing time.
+fn enable_cycle_counter() {
+    // Safety: Only enabling trace and cycle counter, which is idempotent
+    // and does not interfere with other users.
+    let mut cp = unsafe { CorePeripherals::steal() };
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+}
+
+/// Exclude the used part of DMA buffers of the given SAI from caching,
+/// covering its first `length` words by MPU region 1 for SAI1 or region 2 for
+/// SAI2. Region 0 is left to SDRAM.
+///
+/// The region spans the smallest power of two holding the buffers, at least
+/// 32 bytes. Buffers of each SAI are aligned to their full size, so the
+/// region is aligned to its size too.
+///
+/// Refer to ARMv7-M Architecture Reference Manual ARM DDI 0403 Version E.b
+/// Section B3.5.
+fn configure_non_cacheable_dma_buffers(sai: Sai, length: usize) {
+    const REGION_SIZE_MIN: usize = 32;
+    const REGION_EXECUTE_NEVER: u32 = 0x01;
+    const REGION_FULL_ACCESS: u32 = 0x03;
+    // TEX 0b001 with C and B cleared selects normal non-cacheable memory.
+    const REGION_TEX_NORMAL: u32 = 0x01;
+    const REGION_SHAREABLE: u32 = 0x01;
+    const REGION_ENABLE: u32 = 0x01;
+    const MPU_ENABLE: u32 = 0x01;
+    const MPU_DEFAULT_MMAP_FOR_PRIVILEGED: u32 = 0x04;
+
+    let region_number = match sai {
+        Sai::Sai1 => 0x01,
+        #[cfg(feature = "sai2")]
+        Sai::Sai2 => 0x02,
+    };
+    let address = dma_buffers(sai) as usize;
+    let size = (length * core::mem::size_of::<u32>())
+        .next_power_of_two()
+        .max(REGION_SIZE_MIN);
+    let size_field = size.trailing_zeros() - 1;
+
+    // Safety: Only the region of this SAI is modified, which is dedicated to
+    // its DMA buffers. The MPU gets enabled with the default memory map, so
+    // other regions behave as before.
+    let mut cp = unsafe { CorePeripherals::steal() };
+
+    // Write back and drop cached content of the buffers before they stop
+    // being cached.
+    cp.SCB.clean_invalidate_dcache_by_address(address, size);
+
+    unsafe {
+        cortex_m::asm::dmb();
+
+        cp.MPU.rnr.write(region_number);
+        cp.MPU.rbar.write(address as u32);
+        cp.MPU.rasr.write(
+            (REGION_EXECUTE_NEVER << 28)
+                | (REGION_FULL_ACCESS << 24)
+                | (REGION_TEX_NORMAL << 19)
+                | (REGION_SHAREABLE << 18)
+                | (size_field << 1)
+                | REGION_ENABLE,
+        );
+        cp.MPU
+            .ctrl
+            .modify(|r| r | MPU_DEFAULT_MMAP_FOR_PRIVILEGED | MPU_ENABLE);
+
+        // Ensure MPU settings take effect.
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
     }
 }
 
@@ -170,18 +1038,3 @@ fn validate_slice_against_cache_line<T>(slice: &[T]) {
     assert!((addr & (line_size - 1)) == 0);
     assert!((size & (line_size - 1)) == 0);
 }
-
-/// Convert audio data from u24 to f32.
-#[inline(always)]
-fn u24_to_f32(y: u32) -> f32 {
-    let y = (Wrapping(y) + Wrapping(0x0080_0000)).0 & 0x00FF_FFFF; // convert to i32
-    (y as f32 / 8_388_608.0) - 1.0 // (2^24) / 2
-}
-
-/// Convert audio data from f32 to u24.
-#[inline(always)]
-fn f32_to_u24(x: f32) -> u32 {
-    let x = x * 8_388_607.0;
-    let x = x.clamp(-8_388_608.0, 8_388_607.0);
-    (x as i32) as u32
-}
@@ -1,23 +1,127 @@
 //! Interrupt-driven access to the audio interface.
 
 use crate::hal;
+use hal::pac;
 use hal::time;
 
 mod codec;
+pub mod convert;
+mod cpu_load;
+pub mod diagnostics;
 pub mod interface;
+#[cfg(feature = "patch")]
+pub mod patch;
+pub mod sample;
 mod transfer;
 
-pub use interface::{Block, Interface};
+pub use codec::CodecControl;
+#[cfg(feature = "seed_1_1")]
+pub use codec::{AnalogOutput, Headphone, Sidetone};
+pub use cpu_load::CpuLoad;
+pub use diagnostics::Diagnostics;
+pub use interface::{Block, Interface, OutputBlock, Stats};
 
-#[cfg(not(feature = "block_length_64"))]
+/// Interrupt raised by the on-board audio interface whenever a block is
+/// ready for processing, to be served by [`Interface::handle_interrupt`].
+///
+/// It stays the same on all boards and in all [`Mode`]s. Interfaces on SAI2
+/// raise `SAI2_INTERRUPT` instead.
+pub const INTERRUPT: pac::Interrupt = pac::Interrupt::DMA1_STR1;
+
+/// Interrupt raised by the audio interface on SAI2 whenever a block is ready
+/// for processing, to be served by [`Interface::handle_interrupt`], or by
+/// [`Interface::handle_interrupt_dma2_str1`] in TDM mode.
+///
+/// Available with the `sai2` feature.
+#[cfg(feature = "sai2")]
+pub const SAI2_INTERRUPT: pac::Interrupt = pac::Interrupt::DMA2_STR1;
+
+/// Default block length used by [`Interface`].
 pub const BLOCK_LENGTH: usize = 32; // 32 samples
-#[cfg(feature = "block_length_64")]
-pub const BLOCK_LENGTH: usize = 64; // 64 samples
 
-pub const HALF_DMA_BUFFER_LENGTH: usize = BLOCK_LENGTH * 2; // 2 channels
-pub const DMA_BUFFER_LENGTH: usize = HALF_DMA_BUFFER_LENGTH * 2; // 2 half-blocks
+/// Shortest block length supported by [`Interface`].
+pub const MIN_BLOCK_LENGTH: usize = 4; // 4 samples
+/// Longest block length supported by [`Interface`].
+pub const MAX_BLOCK_LENGTH: usize = 256; // 256 samples
+
+const MAX_DMA_BUFFER_LENGTH: usize = MAX_BLOCK_LENGTH * 2 * 2; // 2 channels, 2 half-blocks
+
+/// Sampling rates supported by the audio interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleRate {
+    Hz32000,
+    Hz44100,
+    #[default]
+    Hz48000,
+    Hz88200,
+    Hz96000,
+}
+
+impl SampleRate {
+    pub const fn frequency(self) -> time::Hertz {
+        match self {
+            Self::Hz32000 => time::Hertz::from_raw(32_000),
+            Self::Hz44100 => time::Hertz::from_raw(44_100),
+            Self::Hz48000 => time::Hertz::from_raw(48_000),
+            Self::Hz88200 => time::Hertz::from_raw(88_200),
+            Self::Hz96000 => time::Hertz::from_raw(96_000),
+        }
+    }
+
+    /// Whether the rate runs the codec in its double-speed mode.
+    pub const fn is_double_speed(self) -> bool {
+        matches!(self, Self::Hz88200 | Self::Hz96000)
+    }
+}
+
+/// Directions of audio streamed by the interface on SAI1.
+///
+/// Single-direction modes run only one DMA stream and leave the serial data
+/// line of the other direction free for other use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Both input and output.
+    #[default]
+    Duplex,
+    /// Output only, processed by
+    /// [`Interface::handle_interrupt_dma1_str1_output`].
+    Output,
+    /// Input only, processed by
+    /// [`Interface::handle_interrupt_dma1_str1_input`].
+    Input,
+}
+
+/// Handling of the data cache around DMA buffers of audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DmaCache {
+    /// Buffers are cached. Processed halves get invalidated before reading
+    /// and cleaned after writing on every block.
+    #[default]
+    Maintained,
+    /// Buffers are excluded from caching by an MPU region, skipping cache
+    /// maintenance at the cost of slower access from the callback. Each
+    /// interface gets its own region, covering only the part of the buffers
+    /// used by its block length.
+    NonCacheable,
+}
 
-#[cfg(not(feature = "sampling_rate_96khz"))]
-pub const FS: time::Hertz = time::Hertz::from_raw(48_000);
-#[cfg(feature = "sampling_rate_96khz")]
-pub const FS: time::Hertz = time::Hertz::from_raw(96_000);
+/// Configuration of the audio interface, passed to [`Interface::init`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Config {
+    pub sample_rate: SampleRate,
+    /// Initial content of the output block passed to
+    /// [`Interface::handle_interrupt_dma1_str1_io`].
+    pub output_block: OutputBlock,
+    /// Dithering applied to float output samples.
+    pub dither: sample::Dither,
+    /// Handling of float output samples outside of the range.
+    pub clipping: sample::Clipping,
+    /// Headphone output and analog routing of the WM8731 codec.
+    #[cfg(feature = "seed_1_1")]
+    pub analog_output: AnalogOutput,
+    /// Handling of the data cache around DMA buffers.
+    pub dma_cache: DmaCache,
+    /// Streamed directions. Interfaces on SAI2 support only
+    /// [`Mode::Duplex`].
+    pub mode: Mode,
+}
@@ -0,0 +1,133 @@
+//! Four-channel audio of Daisy Patch.
+//!
+//! Daisy Patch pairs the on-board codec of its Seed, served through SAI1,
+//! with an external AK4556 codec on SAI2, connected to pins 24 to 28 of the
+//! Seed and reset through pin 29. Both SAIs run from the same kernel clock
+//! with the same dividers and get started together, so their frames stay
+//! aligned and a single interrupt serves all four channels.
+
+use super::BLOCK_LENGTH;
+#[cfg(feature = "seed_1_1")]
+use super::codec::AnalogOutput;
+use super::codec::{Ak4556, CodecControl};
+use super::cpu_load::CpuLoad;
+use super::interface::{Error, Stats};
+use crate::hal;
+use hal::gpio;
+use hal::time;
+
+/// Frame of inputs or outputs 1 to 4. The first two channels belong to the
+/// codec of the Seed, the other two to the external codec.
+pub type Frame = [f32; 4];
+pub type Block<const N: usize = BLOCK_LENGTH> = [Frame; N];
+
+pub type CodecPins = (
+    gpio::gpiob::PB14<gpio::Output<gpio::PushPull>>, // PIN_29, reset of AK4556
+);
+
+/// Audio interface of Daisy Patch, processing blocks of `N` four-channel
+/// frames.
+///
+/// It is composed of the [`Interface`](super::Interface) of the on-board
+/// codec and the one created by
+/// [`Interface::init_sai2`](super::Interface::init_sai2), which is usually
+/// done by `board_split_audio!`.
+pub struct Interface<const N: usize = BLOCK_LENGTH> {
+    pub fs: time::Hertz,
+    onboard: super::Interface<N>,
+    external: super::Interface<N>,
+    external_codec: Ak4556<gpio::gpiob::PB14<gpio::Output<gpio::PushPull>>>,
+}
+
+impl<const N: usize> Interface<N> {
+    /// Combine the interface of the on-board codec with the one of SAI2,
+    /// serving the external codec.
+    ///
+    /// Both must be initialized with the same configuration and none of them
+    /// spawned.
+    pub fn new(
+        onboard: super::Interface<N>,
+        external: super::Interface<N>,
+        codec_pins: CodecPins,
+    ) -> Self {
+        Self {
+            fs: onboard.fs,
+            onboard,
+            external,
+            external_codec: Ak4556::init(codec_pins),
+        }
+    }
+
+    /// Start audio streaming.
+    pub fn spawn(mut self) -> Result<Self, Error> {
+        self.restart()?;
+        Ok(self)
+    }
+
+    /// Stop audio streaming of both codecs. See
+    /// [`Interface::stop`](super::Interface::stop).
+    pub fn stop(&mut self) {
+        self.onboard.stop();
+        self.external.stop();
+    }
+
+    /// Start audio streaming again after [`Interface::stop`], going through
+    /// the start sequence of both codecs first.
+    pub fn restart(&mut self) -> Result<(), Error> {
+        self.external_codec.start()?;
+        self.onboard.restart_with(&mut self.external)
+    }
+
+    /// Statistics of audio processing since the start or the last reset.
+    pub fn stats(&self) -> Stats {
+        self.onboard.stats()
+    }
+
+    /// Reset collected statistics of audio processing, including the CPU
+    /// load.
+    pub fn reset_stats(&mut self) {
+        self.onboard.reset_stats();
+    }
+
+    /// Load of the CPU caused by audio processing, as a fraction of the block
+    /// period.
+    pub fn cpu_load(&self) -> &CpuLoad {
+        self.onboard.cpu_load()
+    }
+
+    /// Control of the on-board codec, allowing to adjust its settings at
+    /// runtime. The external AK4556 does not offer any runtime control.
+    pub fn codec(&mut self) -> Option<&mut impl CodecControl> {
+        self.onboard.codec()
+    }
+
+    /// Reconfigure the headphone output and analog routing of the WM8731
+    /// codec. See
+    /// [`Interface::set_analog_output`](super::Interface::set_analog_output).
+    #[cfg(feature = "seed_1_1")]
+    pub fn set_analog_output(&mut self, analog_output: AnalogOutput) -> Result<(), Error> {
+        self.onboard.set_analog_output(analog_output)
+    }
+
+    /// Return whether any float output sample got clipped since the last
+    /// call.
+    pub fn take_clipped(&mut self) -> bool {
+        self.onboard.take_clipped()
+    }
+
+    /// Process a block of four-channel frames, to be called from
+    /// [`INTERRUPT`](super::INTERRUPT).
+    pub fn handle_interrupt(&mut self, callback: impl FnMut(&mut Block<N>)) -> Result<(), Error> {
+        self.handle_interrupt_dma1_str1(callback)
+    }
+
+    /// Process a block of four-channel frames, to be called from the
+    /// `DMA1_STR1` interrupt.
+    pub fn handle_interrupt_dma1_str1(
+        &mut self,
+        callback: impl FnMut(&mut Block<N>),
+    ) -> Result<(), Error> {
+        self.onboard
+            .handle_interrupt_with(&mut self.external, callback)
+    }
+}
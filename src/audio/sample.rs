@@ -0,0 +1,301 @@
+//! Sign-correct conversion between 24-bit codec samples and floats.
+//!
+//! Samples are 24-bit two's complement values stored in the lower bits of
+//! 32-bit DMA words. Floats are scaled so that -1.0 maps to the lowest
+//! sample and every sample value maps to a float exactly, making conversions
+//! lossless in both directions. The highest representable float is therefore
+//! slightly below 1.0.
+//!
+//! Codecs attached in TDM mode may also exchange 16 or 32-bit samples,
+//! selected by [`DataSize`] and converted the same way. Floats cannot hold
+//! all 32 bits, so only the upper 24 bits of 32-bit samples are significant.
+
+use super::convert;
+
+/// Ratio between floats and 24-bit samples, 2^23.
+const SCALE: f32 = 8_388_608.0;
+
+/// Width of samples exchanged with the codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataSize {
+    Bits16,
+    #[default]
+    Bits24,
+    Bits32,
+}
+
+impl DataSize {
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::Bits16 => 16,
+            Self::Bits24 => 24,
+            Self::Bits32 => 32,
+        }
+    }
+
+    /// Ratio between floats and samples, 2^(bits - 1).
+    #[inline(always)]
+    const fn scale(self) -> f32 {
+        (1u32 << (self.bits() - 1)) as f32
+    }
+
+    #[inline(always)]
+    const fn min(self) -> i32 {
+        i32::MIN >> (32 - self.bits())
+    }
+
+    #[inline(always)]
+    const fn max(self) -> i32 {
+        i32::MAX >> (32 - self.bits())
+    }
+
+    /// Convert a sample of this size into float in range `[-1.0, 1.0)`.
+    #[inline(always)]
+    pub fn to_f32(self, y: u32) -> f32 {
+        let shift = 32 - self.bits();
+        ((y << shift) as i32 >> shift) as f32 / self.scale()
+    }
+}
+
+/// Dithering applied when converting floats to 24-bit samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// Round to the nearest sample value.
+    #[default]
+    None,
+    /// Add triangular probability density noise of ±1 LSB before rounding.
+    Tpdf,
+}
+
+/// Handling of floats outside of the range of 24-bit samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Clipping {
+    /// Clip to the lowest or highest sample value.
+    #[default]
+    Hard,
+    /// Clip like [`Clipping::Hard`] and raise a flag that can be read through
+    /// [`Encoder::take_clipped`].
+    Saturate,
+}
+
+/// Convert a 24-bit sample into float in range `[-1.0, 1.0)`.
+#[inline(always)]
+pub fn i24_to_f32(y: u32) -> f32 {
+    convert::u24_to_i32(y) as f32 / SCALE
+}
+
+/// Convert float into a 24-bit sample, rounding to the nearest value and
+/// clipping values outside of the range.
+#[inline(always)]
+pub fn f32_to_i24(x: f32) -> u32 {
+    convert::i32_to_u24(round(x * SCALE))
+}
+
+/// Converter of floats into 24-bit samples with configurable dither and
+/// clipping.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    dither: Dither,
+    clipping: Clipping,
+    seed: u32,
+    clipped: bool,
+}
+
+impl Encoder {
+    pub fn new(dither: Dither, clipping: Clipping) -> Self {
+        Self {
+            dither,
+            clipping,
+            seed: 0x1234_5678,
+            clipped: false,
+        }
+    }
+
+    /// Convert float into a 24-bit sample.
+    #[inline(always)]
+    pub fn encode(&mut self, x: f32) -> u32 {
+        self.encode_as(x, DataSize::Bits24)
+    }
+
+    /// Convert float into a sample of the given size, with dither of ±1 LSB
+    /// of that size.
+    #[inline(always)]
+    pub fn encode_as(&mut self, x: f32, data_size: DataSize) -> u32 {
+        let mut y = x * data_size.scale();
+
+        if self.dither == Dither::Tpdf {
+            y += self.random() - self.random();
+        }
+
+        let (min, max) = (data_size.min(), data_size.max());
+        let y = round(y);
+        if self.clipping == Clipping::Saturate && !(min..=max).contains(&y) {
+            self.clipped = true;
+        }
+
+        to_word(y.clamp(min, max))
+    }
+
+    /// Return whether any sample got clipped since the last call.
+    ///
+    /// This is only tracked with [`Clipping::Saturate`].
+    pub fn take_clipped(&mut self) -> bool {
+        let clipped = self.clipped;
+        self.clipped = false;
+        clipped
+    }
+
+    /// Uniformly distributed noise in range `[0.0, 1.0)`, using xorshift.
+    #[inline(always)]
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / 16_777_216.0 // 2^24
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new(Dither::default(), Clipping::default())
+    }
+}
+
+/// Store the sample in a DMA word. Upper bits are ignored by the codec.
+#[inline(always)]
+fn to_word(y: i32) -> u32 {
+    y as u32
+}
+
+/// Round half away from zero, keeping the conversion symmetric. Large
+/// values saturate to the limits of i32.
+#[inline(always)]
+fn round(x: f32) -> i32 {
+    if x >= 0.0 {
+        (x + 0.5) as i32
+    } else {
+        (x - 0.5) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOWEST: u32 = 0xFF80_0000;
+    const HIGHEST: u32 = 0x007F_FFFF;
+
+    #[test]
+    fn zero_is_converted_to_zero() {
+        assert_eq!(i24_to_f32(0), 0.0);
+        assert_eq!(f32_to_i24(0.0), 0);
+        assert_eq!(f32_to_i24(-0.0), 0);
+    }
+
+    #[test]
+    fn extremes_are_converted_to_full_scale() {
+        assert_eq!(i24_to_f32(0x0080_0000), -1.0);
+        assert_eq!(i24_to_f32(HIGHEST), 1.0 - 1.0 / SCALE);
+        assert_eq!(f32_to_i24(-1.0), LOWEST);
+        assert_eq!(f32_to_i24(1.0 - 1.0 / SCALE), HIGHEST);
+    }
+
+    #[test]
+    fn values_out_of_range_are_clipped() {
+        assert_eq!(f32_to_i24(1.0), HIGHEST);
+        assert_eq!(f32_to_i24(100.0), HIGHEST);
+        assert_eq!(f32_to_i24(-1.5), LOWEST);
+        assert_eq!(f32_to_i24(f32::NEG_INFINITY), LOWEST);
+    }
+
+    #[test]
+    fn conversion_is_symmetric() {
+        for y in [1, 2, 1000, 0x0040_0000, HIGHEST] {
+            let negative = (-(y as i32)) as u32 & 0x00FF_FFFF;
+            assert_eq!(i24_to_f32(negative), -i24_to_f32(y));
+        }
+        for x in [0.1, 0.25, 0.5, 0.999] {
+            assert_eq!(f32_to_i24(-x), (-(f32_to_i24(x) as i32)) as u32);
+        }
+    }
+
+    #[test]
+    fn conversion_round_trips() {
+        for y in (0..=0x00FF_FFFF).step_by(997).chain([0x0080_0000, HIGHEST]) {
+            assert_eq!(f32_to_i24(i24_to_f32(y)) & 0x00FF_FFFF, y);
+        }
+    }
+
+    #[test]
+    fn encoder_without_dither_matches_plain_conversion() {
+        let mut encoder = Encoder::default();
+        for x in [-1.0, -0.3, 0.0, 0.3, 0.999, 2.0] {
+            assert_eq!(encoder.encode(x), f32_to_i24(x));
+        }
+    }
+
+    #[test]
+    fn tpdf_dither_stays_within_one_lsb() {
+        let mut encoder = Encoder::new(Dither::Tpdf, Clipping::Hard);
+        let expected = convert::u24_to_i32(f32_to_i24(0.001));
+        for _ in 0..10_000 {
+            let y = convert::u24_to_i32(encoder.encode(0.001));
+            assert!((y - expected).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn tpdf_dither_preserves_mean() {
+        let mut encoder = Encoder::new(Dither::Tpdf, Clipping::Hard);
+        let mut sum = 0;
+        for _ in 0..10_000 {
+            sum += convert::u24_to_i32(encoder.encode(0.001));
+        }
+        let mean = sum as f32 / 10_000.0;
+        assert!((mean - 0.001 * SCALE).abs() < 0.05);
+    }
+
+    #[test]
+    fn saturation_raises_flag() {
+        let mut encoder = Encoder::new(Dither::None, Clipping::Saturate);
+        assert_eq!(encoder.encode(0.5), f32_to_i24(0.5));
+        assert!(!encoder.take_clipped());
+        assert_eq!(encoder.encode(1.5), HIGHEST);
+        assert!(encoder.take_clipped());
+        assert!(!encoder.take_clipped());
+    }
+
+    #[test]
+    fn data_sizes_convert_extremes_to_full_scale() {
+        assert_eq!(DataSize::Bits16.to_f32(0x8000), -1.0);
+        assert_eq!(DataSize::Bits16.to_f32(0x7FFF), 1.0 - 1.0 / 32_768.0);
+        assert_eq!(DataSize::Bits32.to_f32(0x8000_0000), -1.0);
+        assert_eq!(DataSize::Bits24.to_f32(HIGHEST), i24_to_f32(HIGHEST));
+
+        let mut encoder = Encoder::default();
+        assert_eq!(encoder.encode_as(-1.0, DataSize::Bits16) & 0xFFFF, 0x8000);
+        assert_eq!(encoder.encode_as(2.0, DataSize::Bits16), 0x7FFF);
+        assert_eq!(encoder.encode_as(-1.0, DataSize::Bits32), 0x8000_0000);
+        assert_eq!(encoder.encode_as(2.0, DataSize::Bits32), 0x7FFF_FFFF);
+    }
+
+    #[test]
+    fn data_sizes_round_trip() {
+        let mut encoder = Encoder::default();
+        for y in (0..=0xFFFF).step_by(7) {
+            let x = DataSize::Bits16.to_f32(y);
+            assert_eq!(encoder.encode_as(x, DataSize::Bits16) & 0xFFFF, y);
+        }
+        for y in [0x8000_0000, 0xFFFF_FF00, 0, 0x0000_0100, 0x7FFF_FF00] {
+            let x = DataSize::Bits32.to_f32(y);
+            assert_eq!(encoder.encode_as(x, DataSize::Bits32), y);
+        }
+    }
+
+    #[test]
+    fn hard_clipping_does_not_raise_flag() {
+        let mut encoder = Encoder::new(Dither::None, Clipping::Hard);
+        assert_eq!(encoder.encode(-1.5), LOWEST);
+        assert!(!encoder.take_clipped());
+    }
+}
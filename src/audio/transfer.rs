@@ -1,25 +1,39 @@
 use crate::hal;
 use hal::dma;
+use hal::dma::traits::{DoubleBufferedStream, Stream};
 use hal::gpio;
 use hal::prelude::*;
 use hal::sai::{self, I2sUsers, SaiChannel, SaiI2sExt};
+use hal::traits::i2s::FullDuplex;
 
 use hal::pac;
 
-use super::{DMA_BUFFER_LENGTH, FS};
+use super::Mode;
+#[cfg(feature = "sai2")]
+use super::sample::DataSize;
 
 pub type Sai1Pins = (
     gpio::gpioe::PE2<gpio::Alternate<6>>,         // MCLK_A
     gpio::gpioe::PE5<gpio::Alternate<6>>,         // SCK_A
     gpio::gpioe::PE4<gpio::Alternate<6>>,         // FS_A
-    gpio::gpioe::PE6<gpio::Alternate<6>>,         // SD_A
+    Option<gpio::gpioe::PE6<gpio::Alternate<6>>>, // SD_A
     Option<gpio::gpioe::PE3<gpio::Alternate<6>>>, // SD_B
 );
 
+#[cfg(feature = "sai2")]
+pub type Sai2Pins = (
+    gpio::gpioa::PA1<gpio::Alternate<10>>,          // MCLK_B
+    gpio::gpioa::PA2<gpio::Alternate<8>>,           // SCK_B
+    gpio::gpiog::PG9<gpio::Alternate<10>>,          // FS_B
+    gpio::gpioa::PA0<gpio::Alternate<10>>,          // SD_B
+    Option<gpio::gpiod::PD11<gpio::Alternate<10>>>, // SD_A
+);
+
 pub struct Transfer {
-    transmitter: Transmitter,
-    receiver: Receiver,
+    transmitter: Option<Transmitter>,
+    receiver: Option<Receiver>,
     audio_interface: AudioInterface,
+    buffer_length: u16,
 }
 
 pub struct Config {
@@ -27,6 +41,7 @@ pub struct Config {
     pub rx_channel: Channel,
     pub tx_sync: Sync,
     pub rx_sync: Sync,
+    pub mode: Mode,
 }
 
 pub enum Channel {
@@ -40,27 +55,80 @@ pub enum Sync {
     Slave,
 }
 
+/// Layout of frames transferred over SAI2.
+#[cfg(feature = "sai2")]
+#[derive(Clone, Copy)]
+pub struct Format {
+    /// Number of slots in a frame, 2 for I2S, 4 or 8 for TDM.
+    pub slots: u8,
+    pub data_size: DataSize,
+}
+
+#[cfg(feature = "sai2")]
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            slots: 2,
+            data_size: DataSize::Bits24,
+        }
+    }
+}
+
+/// SAI peripheral used by the transfer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Sai {
+    Sai1,
+    #[cfg(feature = "sai2")]
+    Sai2,
+}
+
 impl Transfer {
     pub fn init(
         clocks: &hal::rcc::CoreClocks,
         sai1_rec: hal::rcc::rec::Sai1,
         sai1_pins: Sai1Pins,
         dma1_rec: hal::rcc::rec::Dma1,
-        tx_buffer: &'static mut [u32; DMA_BUFFER_LENGTH],
-        rx_buffer: &'static mut [u32; DMA_BUFFER_LENGTH],
+        tx_buffer: &'static mut [u32],
+        rx_buffer: &'static mut [u32],
         config: Config,
     ) -> Self {
+        let buffer_length = tx_buffer.len() as u16;
+
         let dma1_streams =
             dma::dma::StreamsTuple::new(unsafe { pac::Peripherals::steal().DMA1 }, dma1_rec);
 
-        let (transmitter, receiver) = match (config.tx_channel, config.rx_channel) {
-            (Channel::A, Channel::B) => (
-                Transmitter::init_with_channel_a(dma1_streams.0, tx_buffer),
-                Receiver::init_with_channel_b(dma1_streams.1, rx_buffer),
+        // The only stream of single-direction modes runs on stream 1, so
+        // the interrupt stays the same in all modes.
+        let (transmitter, receiver) = match (config.tx_channel, config.rx_channel, config.mode) {
+            (Channel::A, Channel::B, Mode::Duplex) => (
+                Some(Transmitter::init_with_channel_a(dma1_streams.0, tx_buffer)),
+                Some(Receiver::init_with_channel_b(dma1_streams.1, rx_buffer)),
+            ),
+            (Channel::A, Channel::B, Mode::Output) => (
+                Some(Transmitter::init_with_channel_a_on_stream_1(
+                    dma1_streams.1,
+                    tx_buffer,
+                )),
+                None,
+            ),
+            (Channel::A, Channel::B, Mode::Input) => (
+                None,
+                Some(Receiver::init_with_channel_b(dma1_streams.1, rx_buffer)),
+            ),
+            (Channel::B, Channel::A, Mode::Duplex) => (
+                Some(Transmitter::init_with_channel_b(dma1_streams.1, tx_buffer)),
+                Some(Receiver::init_with_channel_a(dma1_streams.0, rx_buffer)),
             ),
-            (Channel::B, Channel::A) => (
-                Transmitter::init_with_channel_b(dma1_streams.1, tx_buffer),
-                Receiver::init_with_channel_a(dma1_streams.0, rx_buffer),
+            (Channel::B, Channel::A, Mode::Output) => (
+                Some(Transmitter::init_with_channel_b(dma1_streams.1, tx_buffer)),
+                None,
+            ),
+            (Channel::B, Channel::A, Mode::Input) => (
+                None,
+                Some(Receiver::init_with_channel_a_on_stream_1(
+                    dma1_streams.1,
+                    rx_buffer,
+                )),
             ),
             _ => panic!("Rx and Tx must use different channels"),
         };
@@ -70,49 +138,163 @@ impl Transfer {
             (Sync::Slave, Sync::Master) => (sai::I2SDir::Rx, sai::I2SDir::Tx),
             _ => panic!("There must be only one master and one slave"),
         };
+        // The master block generates clocks even when its data are not
+        // used, the slave block is left out.
+        let slave = match (slave, config.mode) {
+            (sai::I2SDir::Rx, Mode::Output) | (sai::I2SDir::Tx, Mode::Input) => None,
+            _ => Some(slave),
+        };
         let audio_interface = AudioInterface::init(clocks, sai1_rec, sai1_pins, master, slave);
 
         Self {
             transmitter,
             receiver,
             audio_interface,
+            buffer_length,
+        }
+    }
+
+    /// Initialize transfer over SAI2, with its block B running as the master
+    /// receiver and block A as the synchronous slave transmitter.
+    ///
+    /// DMA1 is owned by the transfer of SAI1, this one uses streams 0 and 1
+    /// of DMA2 instead.
+    ///
+    /// Frames consist of slots given by the format. More than two slots
+    /// configure TDM framing.
+    #[cfg(feature = "sai2")]
+    pub fn init_sai2(
+        clocks: &hal::rcc::CoreClocks,
+        sai2_rec: hal::rcc::rec::Sai2,
+        sai2_pins: Sai2Pins,
+        dma2_rec: hal::rcc::rec::Dma2,
+        tx_buffer: &'static mut [u32],
+        rx_buffer: &'static mut [u32],
+        format: Format,
+    ) -> Self {
+        let buffer_length = tx_buffer.len() as u16;
+
+        let dma2_streams =
+            dma::dma::StreamsTuple::new(unsafe { pac::Peripherals::steal().DMA2 }, dma2_rec);
+
+        // The kernel clock of SAI2 is shared with SAI3 and can be only selected
+        // on the whole `PeripheralREC`, which is already partially moved by
+        // now. Select PLL3 P, the same source as of SAI1, directly.
+        unsafe { &*pac::RCC::ptr() }
+            .d2ccip1r
+            .modify(|_, w| w.sai23sel().pll3_p());
+
+        let transmitter = Some(Transmitter::init_sai2_with_channel_a(
+            dma2_streams.0,
+            tx_buffer,
+        ));
+        let receiver = Some(Receiver::init_sai2_with_channel_b(
+            dma2_streams.1,
+            rx_buffer,
+        ));
+        let audio_interface = AudioInterface::init_sai2(clocks, sai2_rec, sai2_pins, format);
+
+        Self {
+            transmitter,
+            receiver,
+            audio_interface,
+            buffer_length,
+        }
+    }
+
+    pub fn sai(&self) -> Sai {
+        match self.audio_interface {
+            AudioInterface::Sai1(_) => Sai::Sai1,
+            #[cfg(feature = "sai2")]
+            AudioInterface::Sai2(_) => Sai::Sai2,
+        }
+    }
+
+    /// Interrupt raised by DMA stream 1, which handles interrupts.
+    pub fn interrupt(&self) -> pac::Interrupt {
+        match self.sai() {
+            Sai::Sai1 => pac::Interrupt::DMA1_STR1,
+            #[cfg(feature = "sai2")]
+            Sai::Sai2 => pac::Interrupt::DMA2_STR1,
         }
     }
 
     pub fn start(&mut self) {
-        let transmitter = &mut self.transmitter;
-        let receiver = &mut self.receiver;
+        unsafe {
+            pac::NVIC::unmask(self.interrupt());
+        }
+
+        self.prepare();
+        self.audio_interface.enable();
+    }
+
+    /// Start this transfer together with the `secondary` one, so both run in
+    /// lock-step and can be served from the interrupt of this one. The
+    /// interrupt of the secondary transfer stays masked.
+    ///
+    /// Both SAIs must be clocked from the same source with the same dividers.
+    /// They get enabled right after each other once their FIFOs are filled,
+    /// the secondary first, so its buffer halves are always transferred by
+    /// the time the interrupt of this transfer is raised.
+    #[cfg(feature = "patch")]
+    pub fn start_with(&mut self, secondary: &mut Transfer) {
+        unsafe {
+            pac::NVIC::unmask(self.interrupt());
+        }
+
+        secondary.prepare();
+        self.prepare();
+        secondary.audio_interface.enable();
+        self.audio_interface.enable();
+    }
+
+    /// Start DMA streams of both channels and let them fill the FIFO of the
+    /// transmitting one, leaving the SAI disabled.
+    fn prepare(&mut self) {
         let audio_interface = &mut self.audio_interface;
 
-        receiver.start(audio_interface);
-        transmitter.start(audio_interface);
+        if let Some(receiver) = &mut self.receiver {
+            receiver.start(audio_interface);
+        }
+        if let Some(transmitter) = &mut self.transmitter {
+            transmitter.start(audio_interface);
+        }
     }
 
-    pub fn examine_interrupt(&mut self) -> Result<State, ()> {
-        // TODO: Decide this during compilation
-        if let Some(channel_b) = self.transmitter.channel_b() {
-            if channel_b.get_half_transfer_flag() {
-                channel_b.clear_half_transfer_interrupt();
-                Ok(State::HalfSent)
-            } else if channel_b.get_transfer_complete_flag() {
-                channel_b.clear_transfer_complete_interrupt();
-                Ok(State::FullSent)
-            } else {
-                Err(())
-            }
-        } else if let Some(channel_b) = self.receiver.channel_b() {
-            if channel_b.get_half_transfer_flag() {
-                channel_b.clear_half_transfer_interrupt();
-                Ok(State::HalfSent)
-            } else if channel_b.get_transfer_complete_flag() {
-                channel_b.clear_transfer_complete_interrupt();
-                Ok(State::FullSent)
-            } else {
-                Err(())
-            }
-        } else {
-            unreachable!("There is always one channel B");
+    /// Stop the transfer, leaving it ready to be started again from the
+    /// beginning of the buffers.
+    pub fn stop(&mut self) {
+        pac::NVIC::mask(self.interrupt());
+
+        self.audio_interface.stop();
+        if let Some(transmitter) = &mut self.transmitter {
+            transmitter.stop(self.buffer_length);
         }
+        if let Some(receiver) = &mut self.receiver {
+            receiver.stop(self.buffer_length);
+        }
+    }
+
+    pub fn examine_interrupt(&mut self) -> Result<State, ()> {
+        self.transmitter
+            .as_mut()
+            .and_then(Transmitter::examine_interrupt)
+            .or_else(|| self.receiver.as_mut().and_then(Receiver::examine_interrupt))
+            .expect("There is always one transfer on stream 1")
+    }
+
+    /// Check whether another half of the buffer got transferred before the
+    /// interrupt of the previous one was examined and processed.
+    pub fn is_interrupt_pending(&mut self) -> bool {
+        self.transmitter
+            .as_mut()
+            .and_then(Transmitter::is_interrupt_pending)
+            .or_else(|| {
+                self.receiver
+                    .as_mut()
+                    .and_then(Receiver::is_interrupt_pending)
+            })
+            .expect("There is always one transfer on stream 1")
     }
 }
 
@@ -121,7 +303,11 @@ pub enum State {
     FullSent,
 }
 
-struct AudioInterface(hal::sai::Sai<pac::SAI1, hal::sai::I2S>);
+enum AudioInterface {
+    Sai1(hal::sai::Sai<pac::SAI1, hal::sai::I2S>),
+    #[cfg(feature = "sai2")]
+    Sai2(hal::sai::Sai<pac::SAI2, hal::sai::I2S>),
+}
 
 impl AudioInterface {
     fn init(
@@ -129,180 +315,455 @@ impl AudioInterface {
         sai1_rec: hal::rcc::rec::Sai1,
         sai1_pins: Sai1Pins,
         master: sai::I2SDir,
-        slave: sai::I2SDir,
+        slave: Option<sai::I2SDir>,
     ) -> Self {
         let mut sai1_master_config =
             sai::I2SChanConfig::new(master).set_frame_sync_active_high(true);
-        let mut sai1_slave_config = sai::I2SChanConfig::new(slave)
-            .set_sync_type(sai::I2SSync::Internal)
-            .set_frame_sync_active_high(true);
+        let mut sai1_slave_config = slave.map(|slave| {
+            sai::I2SChanConfig::new(slave)
+                .set_sync_type(sai::I2SSync::Internal)
+                .set_frame_sync_active_high(true)
+        });
 
         if master == sai::I2SDir::Tx {
             sai1_master_config = sai1_master_config.set_clock_strobe(sai::I2SClockStrobe::Falling);
-            sai1_slave_config = sai1_slave_config.set_clock_strobe(sai::I2SClockStrobe::Rising);
+            sai1_slave_config = sai1_slave_config
+                .map(|config| config.set_clock_strobe(sai::I2SClockStrobe::Rising));
+        }
+
+        let mut users = I2sUsers::new(sai1_master_config);
+        if let Some(sai1_slave_config) = sai1_slave_config {
+            users = users.add_slave(sai1_slave_config);
         }
 
-        Self(unsafe { pac::Peripherals::steal().SAI1 }.i2s_ch_a(
-            sai1_pins,
-            FS,
+        // Safety: Pins are zero-sized markers, the HAL takes them only to
+        // verify their mapping. An unused data line of the master block is
+        // left untouched, free for other use.
+        let (mclk, sck, fs, sd_a, sd_b) = sai1_pins;
+        let sd_a = sd_a.unwrap_or_else(|| unsafe { core::mem::zeroed() });
+
+        Self::Sai1(unsafe { pac::Peripherals::steal().SAI1 }.i2s_ch_a(
+            (mclk, sck, fs, sd_a, sd_b),
+            hal_fs(clocks),
             sai::I2SDataSize::BITS_24,
             sai1_rec,
             clocks,
-            I2sUsers::new(sai1_master_config).add_slave(sai1_slave_config),
+            users,
         ))
     }
+
+    #[cfg(feature = "sai2")]
+    fn init_sai2(
+        clocks: &hal::rcc::CoreClocks,
+        sai2_rec: hal::rcc::rec::Sai2,
+        sai2_pins: Sai2Pins,
+        format: Format,
+    ) -> Self {
+        let tdm = format.slots > 2;
+        let (data_size, slot_bits) = match format.data_size {
+            DataSize::Bits16 => (sai::I2SDataSize::BITS_16, 16),
+            DataSize::Bits24 => (sai::I2SDataSize::BITS_24, 32),
+            DataSize::Bits32 => (sai::I2SDataSize::BITS_32, 32),
+        };
+
+        // The HAL configures a two-slot I2S frame, TDM frames get defined
+        // below instead.
+        let sai2_master_config =
+            sai::I2SChanConfig::new(sai::I2SDir::Rx).set_frame_sync_active_high(true);
+        let sai2_slave_config = sai::I2SChanConfig::new(sai::I2SDir::Tx)
+            .set_sync_type(sai::I2SSync::Internal)
+            .set_frame_sync_active_high(true);
+
+        let sai2 = unsafe { pac::Peripherals::steal().SAI2 }.i2s_ch_b(
+            sai2_pins,
+            hal_fs(clocks),
+            data_size,
+            sai2_rec,
+            clocks,
+            I2sUsers::new(sai2_master_config).add_slave(sai2_slave_config),
+        );
+
+        if tdm {
+            // The HAL cannot express a frame of 256 bits nor a frame sync
+            // shorter than half of the frame, so the whole TDM frame is
+            // configured here, see RM0433 51.4.6 Frame synchronization and
+            // 51.4.7 Slot configuration. Frame sync is a single bit clock
+            // long pulse, starting one bit before the first slot, as
+            // expected by TDM codecs.
+            let frame_bits = u16::from(format.slots) * slot_bits;
+            let slots_enabled = (1 << format.slots) - 1;
+            for channel in &unsafe { &*pac::SAI2::ptr() }.ch {
+                channel.frcr.write(|w| unsafe {
+                    w.frl()
+                        .bits((frame_bits - 1) as u8)
+                        .fsall()
+                        .bits(0)
+                        .fsdef()
+                        .clear_bit()
+                        .fspol()
+                        .set_bit()
+                        .fsoff()
+                        .set_bit()
+                });
+                channel.slotr.modify(|_, w| unsafe {
+                    w.nbslot()
+                        .bits(format.slots - 1)
+                        .sloten()
+                        .bits(slots_enabled)
+                });
+            }
+        }
+
+        Self::Sai2(sai2)
+    }
+
+    fn registers(&self) -> &'static pac::sai4::RegisterBlock {
+        // Safety: Registers are only read, or written by the owner of the
+        // peripheral.
+        match self {
+            Self::Sai1(_) => unsafe { &*pac::SAI1::ptr() },
+            #[cfg(feature = "sai2")]
+            Self::Sai2(_) => unsafe { &*pac::SAI2::ptr() },
+        }
+    }
+
+    fn enable_dma(&mut self, channel: SaiChannel) {
+        match self {
+            Self::Sai1(sai1) => sai1.enable_dma(channel),
+            #[cfg(feature = "sai2")]
+            Self::Sai2(sai2) => sai2.enable_dma(channel),
+        }
+    }
+
+    /// Enable DMA requests of the transmitting channel and wait until its
+    /// FIFO gets filled.
+    fn start_transmission(&mut self, channel: SaiChannel) {
+        self.enable_dma(channel);
+        let fifo = &self.registers().ch[channel as usize];
+        while fifo.sr.read().flvl().is_empty() {} // wait until sai's fifo starts to receive data
+    }
+
+    /// Start the audio interface.
+    fn enable(&mut self) {
+        match self {
+            // Sending fails without a transmitting block in input-only mode.
+            Self::Sai1(sai1) => {
+                sai1.enable();
+                let _ = sai1.try_send(0, 0);
+            }
+            #[cfg(feature = "sai2")]
+            Self::Sai2(sai2) => {
+                sai2.enable();
+                sai2.try_send(0, 0).unwrap();
+            }
+        }
+    }
+
+    /// Disable SAI following RM0433 51.4.15, its DMA requests and flush
+    /// its FIFOs.
+    fn stop(&mut self) {
+        match self {
+            Self::Sai1(sai1) => sai1.disable(),
+            #[cfg(feature = "sai2")]
+            Self::Sai2(sai2) => sai2.disable(),
+        }
+
+        for channel in &self.registers().ch {
+            while channel.cr1.read().saien().bit_is_set() {}
+            channel.cr1.modify(|_, w| w.dmaen().disabled());
+            channel.cr2.modify(|_, w| w.fflush().flush());
+        }
+    }
 }
 
-type _Transmitter<S, C> = dma::Transfer<
-    S,
-    C,
-    dma::MemoryToPeripheral,
-    &'static mut [u32; DMA_BUFFER_LENGTH],
-    dma::DBTransfer,
->;
+/// Sampling rate to be passed to the HAL when initializing SAI.
+///
+/// PLL3 gets retuned to 256 × fs after the clock tree is frozen. The HAL
+/// derives the MCLK divider from the frozen PLL3 frequency, so request the
+/// rate that keeps the divider at 1.
+fn hal_fs(clocks: &hal::rcc::CoreClocks) -> hal::time::Hertz {
+    clocks.pll3_p_ck().unwrap() / 256
+}
+
+type _Transmitter<S, C> =
+    dma::Transfer<S, C, dma::MemoryToPeripheral, &'static mut [u32], dma::DBTransfer>;
 
 enum Transmitter {
     ChannelA(_Transmitter<dma::dma::Stream0<pac::DMA1>, sai::dma::ChannelA<pac::SAI1>>),
     ChannelB(_Transmitter<dma::dma::Stream1<pac::DMA1>, sai::dma::ChannelB<pac::SAI1>>),
+    ChannelAOnStream1(_Transmitter<dma::dma::Stream1<pac::DMA1>, sai::dma::ChannelA<pac::SAI1>>),
+    #[cfg(feature = "sai2")]
+    Sai2ChannelA(_Transmitter<dma::dma::Stream0<pac::DMA2>, sai::dma::ChannelA<pac::SAI2>>),
 }
 
 impl Transmitter {
     fn init_with_channel_a(
         dma1_str0: dma::dma::Stream0<pac::DMA1>,
-        tx_buffer: &'static mut [u32; DMA_BUFFER_LENGTH],
+        tx_buffer: &'static mut [u32],
     ) -> Self {
         Transmitter::ChannelA(dma::Transfer::init(
             dma1_str0,
             unsafe { pac::Peripherals::steal().SAI1.dma_ch_a() },
             tx_buffer,
             None,
-            channel_a_dma_config(),
+            dma_config(),
         ))
     }
 
     fn init_with_channel_b(
         dma1_str1: dma::dma::Stream1<pac::DMA1>,
-        tx_buffer: &'static mut [u32; DMA_BUFFER_LENGTH],
+        tx_buffer: &'static mut [u32],
     ) -> Self {
         Transmitter::ChannelB(dma::Transfer::init(
             dma1_str1,
             unsafe { pac::Peripherals::steal().SAI1.dma_ch_b() },
             tx_buffer,
             None,
-            channel_b_dma_config(),
+            stream_1_dma_config(),
+        ))
+    }
+
+    fn init_with_channel_a_on_stream_1(
+        dma1_str1: dma::dma::Stream1<pac::DMA1>,
+        tx_buffer: &'static mut [u32],
+    ) -> Self {
+        Transmitter::ChannelAOnStream1(dma::Transfer::init(
+            dma1_str1,
+            unsafe { pac::Peripherals::steal().SAI1.dma_ch_a() },
+            tx_buffer,
+            None,
+            stream_1_dma_config(),
+        ))
+    }
+
+    #[cfg(feature = "sai2")]
+    fn init_sai2_with_channel_a(
+        dma2_str0: dma::dma::Stream0<pac::DMA2>,
+        tx_buffer: &'static mut [u32],
+    ) -> Self {
+        Transmitter::Sai2ChannelA(dma::Transfer::init(
+            dma2_str0,
+            unsafe { pac::Peripherals::steal().SAI2.dma_ch_a() },
+            tx_buffer,
+            None,
+            dma_config(),
         ))
     }
 
     fn start(&mut self, audio_interface: &mut AudioInterface) {
-        let sai1 = &mut audio_interface.0;
         match self {
             Transmitter::ChannelA(dma1_str0) => {
-                dma1_str0.start(|sai1_rb| {
-                    sai1.enable_dma(SaiChannel::ChannelA);
-                    while sai1_rb.cha().sr.read().flvl().is_empty() {} // wait until sai1's fifo starts to receive data
-                    sai1.enable();
-                    use hal::traits::i2s::FullDuplex;
-                    sai1.try_send(0, 0).unwrap();
-                });
+                dma1_str0.start(|_| audio_interface.start_transmission(SaiChannel::ChannelA));
             }
             Transmitter::ChannelB(dma1_str1) => {
-                unsafe {
-                    pac::NVIC::unmask(pac::Interrupt::DMA1_STR1);
-                }
-                dma1_str1.start(|sai1_rb| {
-                    sai1.enable_dma(SaiChannel::ChannelB);
-                    while sai1_rb.chb().sr.read().flvl().is_empty() {} // wait until sai1's fifo starts to receive data
-                    sai1.enable();
-                    use hal::traits::i2s::FullDuplex;
-                    sai1.try_send(0, 0).unwrap();
-                });
+                dma1_str1.start(|_| audio_interface.start_transmission(SaiChannel::ChannelB));
+            }
+            Transmitter::ChannelAOnStream1(dma1_str1) => {
+                dma1_str1.start(|_| audio_interface.start_transmission(SaiChannel::ChannelA));
+            }
+            #[cfg(feature = "sai2")]
+            Transmitter::Sai2ChannelA(dma2_str0) => {
+                dma2_str0.start(|_| audio_interface.start_transmission(SaiChannel::ChannelA));
             }
         }
     }
 
-    fn channel_b(
-        &mut self,
-    ) -> Option<&mut _Transmitter<dma::dma::Stream1<pac::DMA1>, sai::dma::ChannelB<pac::SAI1>>>
-    {
-        if let Self::ChannelB(channel_b) = self {
-            Some(channel_b)
-        } else {
-            None
+    fn stop(&mut self, length: u16) {
+        match self {
+            Transmitter::ChannelA(dma1_str0) => stop_stream(dma1_str0, length),
+            Transmitter::ChannelB(dma1_str1) => stop_stream(dma1_str1, length),
+            Transmitter::ChannelAOnStream1(dma1_str1) => stop_stream(dma1_str1, length),
+            #[cfg(feature = "sai2")]
+            Transmitter::Sai2ChannelA(dma2_str0) => stop_stream(dma2_str0, length),
+        }
+    }
+
+    fn examine_interrupt(&mut self) -> Option<Result<State, ()>> {
+        match self {
+            Self::ChannelB(dma1_str1) => Some(examine_stream(dma1_str1)),
+            Self::ChannelAOnStream1(dma1_str1) => Some(examine_stream(dma1_str1)),
+            Self::ChannelA(_) => None,
+            #[cfg(feature = "sai2")]
+            Self::Sai2ChannelA(_) => None,
+        }
+    }
+
+    fn is_interrupt_pending(&mut self) -> Option<bool> {
+        match self {
+            Self::ChannelB(dma1_str1) => Some(is_stream_pending(dma1_str1)),
+            Self::ChannelAOnStream1(dma1_str1) => Some(is_stream_pending(dma1_str1)),
+            Self::ChannelA(_) => None,
+            #[cfg(feature = "sai2")]
+            Self::Sai2ChannelA(_) => None,
         }
     }
 }
 
-type _Receiver<S, C> = dma::Transfer<
-    S,
-    C,
-    dma::PeripheralToMemory,
-    &'static mut [u32; DMA_BUFFER_LENGTH],
-    dma::DBTransfer,
->;
+type _Receiver<S, C> =
+    dma::Transfer<S, C, dma::PeripheralToMemory, &'static mut [u32], dma::DBTransfer>;
 
 enum Receiver {
     ChannelA(_Receiver<dma::dma::Stream0<pac::DMA1>, sai::dma::ChannelA<pac::SAI1>>),
     ChannelB(_Receiver<dma::dma::Stream1<pac::DMA1>, sai::dma::ChannelB<pac::SAI1>>),
+    ChannelAOnStream1(_Receiver<dma::dma::Stream1<pac::DMA1>, sai::dma::ChannelA<pac::SAI1>>),
+    #[cfg(feature = "sai2")]
+    Sai2ChannelB(_Receiver<dma::dma::Stream1<pac::DMA2>, sai::dma::ChannelB<pac::SAI2>>),
 }
 
 impl Receiver {
     fn init_with_channel_a(
         dma1_str0: dma::dma::Stream0<pac::DMA1>,
-        rx_buffer: &'static mut [u32; DMA_BUFFER_LENGTH],
+        rx_buffer: &'static mut [u32],
     ) -> Self {
         Receiver::ChannelA(dma::Transfer::init(
             dma1_str0,
             unsafe { pac::Peripherals::steal().SAI1.dma_ch_a() },
             rx_buffer,
             None,
-            channel_a_dma_config(),
+            dma_config(),
         ))
     }
 
     fn init_with_channel_b(
         dma1_str1: dma::dma::Stream1<pac::DMA1>,
-        rx_buffer: &'static mut [u32; DMA_BUFFER_LENGTH],
+        rx_buffer: &'static mut [u32],
     ) -> Self {
         Receiver::ChannelB(dma::Transfer::init(
             dma1_str1,
             unsafe { pac::Peripherals::steal().SAI1.dma_ch_b() },
             rx_buffer,
             None,
-            channel_b_dma_config(),
+            stream_1_dma_config(),
+        ))
+    }
+
+    fn init_with_channel_a_on_stream_1(
+        dma1_str1: dma::dma::Stream1<pac::DMA1>,
+        rx_buffer: &'static mut [u32],
+    ) -> Self {
+        Receiver::ChannelAOnStream1(dma::Transfer::init(
+            dma1_str1,
+            unsafe { pac::Peripherals::steal().SAI1.dma_ch_a() },
+            rx_buffer,
+            None,
+            stream_1_dma_config(),
+        ))
+    }
+
+    #[cfg(feature = "sai2")]
+    fn init_sai2_with_channel_b(
+        dma2_str1: dma::dma::Stream1<pac::DMA2>,
+        rx_buffer: &'static mut [u32],
+    ) -> Self {
+        Receiver::Sai2ChannelB(dma::Transfer::init(
+            dma2_str1,
+            unsafe { pac::Peripherals::steal().SAI2.dma_ch_b() },
+            rx_buffer,
+            None,
+            stream_1_dma_config(),
         ))
     }
 
     fn start(&mut self, audio_interface: &mut AudioInterface) {
-        let sai1 = &mut audio_interface.0;
         match self {
             Receiver::ChannelA(dma1_str0) => {
-                dma1_str0.start(|_sai1_rb| {
-                    sai1.enable_dma(SaiChannel::ChannelA);
-                });
+                dma1_str0.start(|_| audio_interface.enable_dma(SaiChannel::ChannelA));
             }
             Receiver::ChannelB(dma1_str1) => {
-                unsafe {
-                    pac::NVIC::unmask(pac::Interrupt::DMA1_STR1);
-                }
-                dma1_str1.start(|_sai1_rb| {
-                    sai1.enable_dma(SaiChannel::ChannelB);
-                });
+                dma1_str1.start(|_| audio_interface.enable_dma(SaiChannel::ChannelB));
+            }
+            Receiver::ChannelAOnStream1(dma1_str1) => {
+                dma1_str1.start(|_| audio_interface.enable_dma(SaiChannel::ChannelA));
             }
+            #[cfg(feature = "sai2")]
+            Receiver::Sai2ChannelB(dma2_str1) => {
+                dma2_str1.start(|_| audio_interface.enable_dma(SaiChannel::ChannelB));
+            }
+        }
+    }
+
+    fn stop(&mut self, length: u16) {
+        match self {
+            Receiver::ChannelA(dma1_str0) => stop_stream(dma1_str0, length),
+            Receiver::ChannelB(dma1_str1) => stop_stream(dma1_str1, length),
+            Receiver::ChannelAOnStream1(dma1_str1) => stop_stream(dma1_str1, length),
+            #[cfg(feature = "sai2")]
+            Receiver::Sai2ChannelB(dma2_str1) => stop_stream(dma2_str1, length),
+        }
+    }
+
+    fn examine_interrupt(&mut self) -> Option<Result<State, ()>> {
+        match self {
+            Self::ChannelB(channel_b) => Some(examine_stream(channel_b)),
+            Self::ChannelAOnStream1(channel_a) => Some(examine_stream(channel_a)),
+            #[cfg(feature = "sai2")]
+            Self::Sai2ChannelB(channel_b) => Some(examine_stream(channel_b)),
+            Self::ChannelA(_) => None,
         }
     }
 
-    fn channel_b(
-        &mut self,
-    ) -> Option<&mut _Receiver<dma::dma::Stream1<pac::DMA1>, sai::dma::ChannelB<pac::SAI1>>> {
-        if let Self::ChannelB(channel_b) = self {
-            Some(channel_b)
-        } else {
-            None
+    fn is_interrupt_pending(&mut self) -> Option<bool> {
+        match self {
+            Self::ChannelB(channel_b) => Some(is_stream_pending(channel_b)),
+            Self::ChannelAOnStream1(channel_a) => Some(is_stream_pending(channel_a)),
+            #[cfg(feature = "sai2")]
+            Self::Sai2ChannelB(channel_b) => Some(is_stream_pending(channel_b)),
+            Self::ChannelA(_) => None,
         }
     }
 }
 
-fn channel_a_dma_config() -> dma::dma::DmaConfig {
+/// Check which half of the buffer was transferred and acknowledge the
+/// interrupt.
+fn examine_stream<S, P, D>(
+    transfer: &mut dma::Transfer<S, P, D, &'static mut [u32], dma::DBTransfer>,
+) -> Result<State, ()>
+where
+    S: Stream<Config = dma::dma::DmaConfig> + DoubleBufferedStream,
+    D: dma::traits::Direction,
+    P: dma::traits::TargetAddress<D, MemSize = u32>,
+{
+    if transfer.get_half_transfer_flag() {
+        transfer.clear_half_transfer_interrupt();
+        Ok(State::HalfSent)
+    } else if transfer.get_transfer_complete_flag() {
+        transfer.clear_transfer_complete_interrupt();
+        Ok(State::FullSent)
+    } else {
+        Err(())
+    }
+}
+
+fn is_stream_pending<S, P, D>(
+    transfer: &mut dma::Transfer<S, P, D, &'static mut [u32], dma::DBTransfer>,
+) -> bool
+where
+    S: Stream<Config = dma::dma::DmaConfig> + DoubleBufferedStream,
+    D: dma::traits::Direction,
+    P: dma::traits::TargetAddress<D, MemSize = u32>,
+{
+    transfer.get_half_transfer_flag() || transfer.get_transfer_complete_flag()
+}
+
+/// Disable the DMA stream and rewind it to the beginning of its buffer.
+fn stop_stream<S, P, D>(
+    transfer: &mut dma::Transfer<S, P, D, &'static mut [u32], dma::DBTransfer>,
+    length: u16,
+) where
+    S: Stream<Config = dma::dma::DmaConfig> + DoubleBufferedStream,
+    D: dma::traits::Direction,
+    P: dma::traits::TargetAddress<D, MemSize = u32>,
+{
+    transfer.pause(|_| {});
+    transfer.clear_interrupts();
+
+    // Safety: The stream is disabled and keeps its configuration, only the
+    // number of transfers is reset to cover the whole buffer again.
+    let stream = unsafe { transfer.get_stream() };
+    stream.set_number_of_transfers(length);
+}
+
+fn dma_config() -> dma::dma::DmaConfig {
     dma::dma::DmaConfig::default()
         .priority(dma::config::Priority::High)
         .memory_increment(true)
@@ -311,9 +772,9 @@ fn channel_a_dma_config() -> dma::dma::DmaConfig {
         .fifo_enable(false)
 }
 
-// Since channel B is always tied to DMA stream 1, it will be the one
-// handling interrupts.
-fn channel_b_dma_config() -> dma::dma::DmaConfig {
+// Stream 1 serves channel B, or the only channel of single-direction modes,
+// so it is the one handling interrupts.
+fn stream_1_dma_config() -> dma::dma::DmaConfig {
     dma::dma::DmaConfig::default()
         .priority(dma::config::Priority::High)
         .memory_increment(true)
@@ -256,19 +256,62 @@ macro_rules! board_split_gpios {
     }};
 }
 
-/// Configure audio codec and return its handle.
+/// Configure serial data pins of SAI1 used in the given mode, leaving the
+/// other one untouched. Used by [`board_split_onboard_audio`].
+#[doc(hidden)]
+#[cfg(any(feature = "seed", feature = "seed_1_2"))]
+#[macro_export]
+macro_rules! board_split_sai1_data {
+    ($pins:expr, duplex) => {
+        (
+            Some($pins.SAI.SD_A.into_alternate::<6>()),
+            Some($pins.SAI.SD_B.into_alternate::<6>()),
+        )
+    };
+    ($pins:expr, output) => {
+        (Some($pins.SAI.SD_A.into_alternate::<6>()), None)
+    };
+    ($pins:expr, input) => {
+        (None, Some($pins.SAI.SD_B.into_alternate::<6>()))
+    };
+}
+
+/// Configure serial data pins of SAI1 used in the given mode, leaving the
+/// other one untouched. Used by [`board_split_onboard_audio`].
+#[doc(hidden)]
+#[cfg(any(feature = "seed_1_1", feature = "patch_sm"))]
+#[macro_export]
+macro_rules! board_split_sai1_data {
+    ($pins:expr, duplex) => {
+        (
+            Some($pins.SAI.SD_A.into_alternate::<6>()),
+            Some($pins.SAI.SD_B.into_alternate::<6>()),
+        )
+    };
+    ($pins:expr, output) => {
+        (None, Some($pins.SAI.SD_B.into_alternate::<6>()))
+    };
+    ($pins:expr, input) => {
+        (Some($pins.SAI.SD_A.into_alternate::<6>()), None)
+    };
+}
+
+/// Configure the on-board audio codec and return its handle, used by
+/// [`board_split_audio`].
+#[doc(hidden)]
 #[cfg(feature = "seed")]
 #[macro_export]
-macro_rules! board_split_audio {
-    ($ccdr:expr, $pins:expr) => {{
+macro_rules! board_split_onboard_audio {
+    ($ccdr:expr, $pins:expr, $config:expr, $mode:ident) => {{
+        let (sd_a, sd_b) = daisy::board_split_sai1_data!($pins, $mode);
         let codec_pins = ($pins.CODEC.PDN.into_push_pull_output(),);
 
         let sai1_pins = (
             $pins.SAI.MCLK_A.into_alternate::<6>(),
             $pins.SAI.SCK_A.into_alternate::<6>(),
             $pins.SAI.FS_A.into_alternate::<6>(),
-            $pins.SAI.SD_A.into_alternate::<6>(),
-            Some($pins.SAI.SD_B.into_alternate::<6>()),
+            sd_a,
+            sd_b,
         );
 
         let sai1_prec = $ccdr
@@ -283,24 +326,28 @@ macro_rules! board_split_audio {
             codec_pins,
             $ccdr.peripheral.I2C2,
             $ccdr.peripheral.DMA1,
+            $config,
         )
         .unwrap()
     }};
 }
 
-/// Configure audio codec and return its handle.
+/// Configure the on-board audio codec and return its handle, used by
+/// [`board_split_audio`].
+#[doc(hidden)]
 #[cfg(feature = "seed_1_2")]
 #[macro_export]
-macro_rules! board_split_audio {
-    ($ccdr:expr, $pins:expr) => {{
+macro_rules! board_split_onboard_audio {
+    ($ccdr:expr, $pins:expr, $config:expr, $mode:ident) => {{
+        let (sd_a, sd_b) = daisy::board_split_sai1_data!($pins, $mode);
         let codec_pins = ($pins.CODEC.DEMP.into_push_pull_output(),);
 
         let sai1_pins = (
             $pins.SAI.MCLK_A.into_alternate::<6>(),
             $pins.SAI.SCK_A.into_alternate::<6>(),
             $pins.SAI.FS_A.into_alternate::<6>(),
-            $pins.SAI.SD_A.into_alternate::<6>(),
-            Some($pins.SAI.SD_B.into_alternate::<6>()),
+            sd_a,
+            sd_b,
         );
 
         let sai1_prec = $ccdr
@@ -315,16 +362,20 @@ macro_rules! board_split_audio {
             codec_pins,
             $ccdr.peripheral.I2C2,
             $ccdr.peripheral.DMA1,
+            $config,
         )
         .unwrap()
     }};
 }
 
-/// Configure audio codec and return its handle.
+/// Configure the on-board audio codec and return its handle, used by
+/// [`board_split_audio`].
+#[doc(hidden)]
 #[cfg(any(feature = "seed_1_1", feature = "patch_sm"))]
 #[macro_export]
-macro_rules! board_split_audio {
-    ($ccdr:expr, $pins:expr) => {{
+macro_rules! board_split_onboard_audio {
+    ($ccdr:expr, $pins:expr, $config:expr, $mode:ident) => {{
+        let (sd_a, sd_b) = daisy::board_split_sai1_data!($pins, $mode);
         let codec_pins = (
             $pins.CODEC.SCL.into_alternate::<4>().set_open_drain(),
             $pins.CODEC.SDA.into_alternate::<4>().set_open_drain(),
@@ -334,8 +385,8 @@ macro_rules! board_split_audio {
             $pins.SAI.MCLK_A.into_alternate::<6>(),
             $pins.SAI.SCK_A.into_alternate::<6>(),
             $pins.SAI.FS_A.into_alternate::<6>(),
-            $pins.SAI.SD_A.into_alternate::<6>(),
-            Some($pins.SAI.SD_B.into_alternate::<6>()),
+            sd_a,
+            sd_b,
         );
 
         let sai1_prec = $ccdr
@@ -350,11 +401,248 @@ macro_rules! board_split_audio {
             codec_pins,
             $ccdr.peripheral.I2C2,
             $ccdr.peripheral.DMA1,
+            $config,
         )
         .unwrap()
     }};
 }
 
+/// Configure audio codec and return its handle.
+///
+/// Optionally takes [`audio::Config`](crate::audio::Config) as the last
+/// argument, default configuration is used otherwise.
+#[cfg(not(feature = "patch"))]
+#[macro_export]
+macro_rules! board_split_audio {
+    ($ccdr:expr, $pins:expr) => {{ daisy::board_split_audio!($ccdr, $pins, daisy::audio::Config::default()) }};
+    ($ccdr:expr, $pins:expr, $config:expr) => {{ daisy::board_split_onboard_audio!($ccdr, $pins, $config, duplex) }};
+}
+
+/// Configure audio codec for output only and return its handle, processed
+/// by [`audio::Interface::handle_interrupt_dma1_str1_output`].
+///
+/// The serial data pin of audio input is left unconfigured, free for other
+/// use. Optionally takes [`audio::Config`](crate::audio::Config) as the last
+/// argument, default configuration is used otherwise. Its mode is
+/// overridden.
+///
+/// [`audio::Interface::handle_interrupt_dma1_str1_output`]: crate::audio::Interface::handle_interrupt_dma1_str1_output
+#[cfg(not(feature = "patch"))]
+#[macro_export]
+macro_rules! board_split_audio_output {
+    ($ccdr:expr, $pins:expr) => {{ daisy::board_split_audio_output!($ccdr, $pins, daisy::audio::Config::default()) }};
+    ($ccdr:expr, $pins:expr, $config:expr) => {{
+        let config = daisy::audio::Config {
+            mode: daisy::audio::Mode::Output,
+            ..$config
+        };
+        daisy::board_split_onboard_audio!($ccdr, $pins, config, output)
+    }};
+}
+
+/// Configure audio codec for input only and return its handle, processed
+/// by [`audio::Interface::handle_interrupt_dma1_str1_input`].
+///
+/// The serial data pin of audio output is left unconfigured, free for other
+/// use. Optionally takes [`audio::Config`](crate::audio::Config) as the last
+/// argument, default configuration is used otherwise. Its mode is
+/// overridden.
+///
+/// [`audio::Interface::handle_interrupt_dma1_str1_input`]: crate::audio::Interface::handle_interrupt_dma1_str1_input
+#[cfg(not(feature = "patch"))]
+#[macro_export]
+macro_rules! board_split_audio_input {
+    ($ccdr:expr, $pins:expr) => {{ daisy::board_split_audio_input!($ccdr, $pins, daisy::audio::Config::default()) }};
+    ($ccdr:expr, $pins:expr, $config:expr) => {{
+        let config = daisy::audio::Config {
+            mode: daisy::audio::Mode::Input,
+            ..$config
+        };
+        daisy::board_split_onboard_audio!($ccdr, $pins, config, input)
+    }};
+}
+
+/// Configure both audio codecs of Daisy Patch and return the handle of their
+/// four-channel [`audio::patch::Interface`](crate::audio::patch::Interface).
+///
+/// Optionally takes [`audio::Config`](crate::audio::Config) as the last
+/// argument, default configuration is used otherwise.
+#[cfg(feature = "patch")]
+#[macro_export]
+macro_rules! board_split_audio {
+    ($ccdr:expr, $pins:expr) => {{ daisy::board_split_audio!($ccdr, $pins, daisy::audio::Config::default()) }};
+    ($ccdr:expr, $pins:expr, $config:expr) => {{
+        let config = $config;
+        let onboard = daisy::board_split_onboard_audio!($ccdr, $pins, config, duplex);
+        let external = daisy::board_split_audio_sai2!($ccdr, $pins, config);
+        let codec_pins = ($pins.GPIO.PIN_29.into_push_pull_output(),);
+        daisy::audio::patch::Interface::new(onboard, external, codec_pins)
+    }};
+}
+
+/// Configure the second audio interface on SAI2 and return its handle.
+///
+/// It serves an external codec connected to pins 24 to 28, clocked in sync
+/// with the on-board codec. Must be called after [`board_split_audio`], with
+/// the same [`audio::Config`](crate::audio::Config) if one was passed there.
+///
+/// Available with the `sai2` feature.
+#[cfg(all(
+    feature = "sai2",
+    any(feature = "seed", feature = "seed_1_1", feature = "seed_1_2")
+))]
+#[macro_export]
+macro_rules! board_split_audio_sai2 {
+    ($ccdr:expr, $pins:expr) => {{ daisy::board_split_audio_sai2!($ccdr, $pins, daisy::audio::Config::default()) }};
+    ($ccdr:expr, $pins:expr, $config:expr) => {{
+        let sai2_pins = (
+            $pins.GPIO.PIN_24.into_alternate::<10>(),
+            $pins.GPIO.PIN_28.into_alternate::<8>(),
+            $pins.GPIO.PIN_27.into_alternate::<10>(),
+            $pins.GPIO.PIN_25.into_alternate::<10>(),
+            Some($pins.GPIO.PIN_26.into_alternate::<10>()),
+        );
+
+        daisy::audio::Interface::init_sai2(
+            &$ccdr.clocks,
+            $ccdr.peripheral.SAI2,
+            sai2_pins,
+            $ccdr.peripheral.DMA2,
+            $config,
+        )
+        .unwrap()
+    }};
+}
+
+/// Configure the second audio interface on SAI2 in TDM mode and return its
+/// handle. TDM is not available on SAI1, which is hardwired to the on-board
+/// codec.
+///
+/// Takes the [`DataSize`](crate::audio::sample::DataSize) of samples, the
+/// number of slots is given by the type of the returned
+/// [`Interface`](crate::audio::Interface). Optionally takes
+/// [`audio::Config`](crate::audio::Config) as the last argument, default
+/// configuration is used otherwise. See [`board_split_audio_sai2`] for
+/// details.
+#[cfg(all(
+    feature = "sai2",
+    any(feature = "seed", feature = "seed_1_1", feature = "seed_1_2")
+))]
+#[macro_export]
+macro_rules! board_split_audio_sai2_tdm {
+    ($ccdr:expr, $pins:expr, $data_size:expr) => {{
+        daisy::board_split_audio_sai2_tdm!(
+            $ccdr,
+            $pins,
+            $data_size,
+            daisy::audio::Config::default()
+        )
+    }};
+    ($ccdr:expr, $pins:expr, $data_size:expr, $config:expr) => {{
+        let sai2_pins = (
+            $pins.GPIO.PIN_24.into_alternate::<10>(),
+            $pins.GPIO.PIN_28.into_alternate::<8>(),
+            $pins.GPIO.PIN_27.into_alternate::<10>(),
+            $pins.GPIO.PIN_25.into_alternate::<10>(),
+            Some($pins.GPIO.PIN_26.into_alternate::<10>()),
+        );
+
+        daisy::audio::Interface::init_sai2_tdm(
+            &$ccdr.clocks,
+            $ccdr.peripheral.SAI2,
+            sai2_pins,
+            $ccdr.peripheral.DMA2,
+            $data_size,
+            $config,
+        )
+        .unwrap()
+    }};
+}
+
+/// Define the handler of the audio interrupt, running the given block.
+///
+/// Without further arguments, this is the interrupt of the on-board audio
+/// interface on SAI1, [`audio::INTERRUPT`](crate::audio::INTERRUPT). With
+/// `sai2` as the first argument, it is the interrupt of the interface on
+/// SAI2, `audio::SAI2_INTERRUPT`, available with the `sai2` feature.
+///
+/// The handler is verified by [`assert_audio_interrupt`] to match the
+/// interrupt raised by this crate, so the build breaks instead of the audio
+/// stalling if they ever diverge:
+///
+/// ```
+/// daisy::audio_interrupt!({
+///     cortex_m::interrupt::free(|cs| {
+///         if let Some(audio) = AUDIO_INTERFACE.borrow(cs).borrow_mut().as_mut() {
+///             audio.handle_interrupt(|block| process(block)).unwrap();
+///         }
+///     });
+/// });
+///
+/// daisy::audio_interrupt!(sai2, {
+///     cortex_m::interrupt::free(|cs| {
+///         if let Some(tdm) = TDM_INTERFACE.borrow(cs).borrow_mut().as_mut() {
+///             tdm.handle_interrupt_dma2_str1(|block| process_tdm(block)).unwrap();
+///         }
+///     });
+/// });
+/// ```
+#[macro_export]
+macro_rules! audio_interrupt {
+    (sai2, $body:block) => {
+        daisy::assert_audio_interrupt!(sai2, DMA2_STR1);
+
+        const _: () = {
+            use daisy::hal::pac::interrupt;
+
+            #[interrupt]
+            fn DMA2_STR1() $body
+        };
+    };
+    ($body:block) => {
+        daisy::assert_audio_interrupt!(DMA1_STR1);
+
+        const _: () = {
+            use daisy::hal::pac::interrupt;
+
+            #[interrupt]
+            fn DMA1_STR1() $body
+        };
+    };
+}
+
+/// Verify at compile time that the given interrupt is
+/// [`audio::INTERRUPT`](crate::audio::INTERRUPT) of the on-board audio
+/// interface, or `audio::SAI2_INTERRUPT` when preceded by `sai2`.
+///
+/// RTIC requires the interrupt name to be spelled out in `binds`. Placed
+/// next to the task bound to it, this breaks the build once the name stops
+/// matching:
+///
+/// ```
+/// daisy::assert_audio_interrupt!(DMA1_STR1);
+///
+/// #[task(binds = DMA1_STR1, local = [audio_interface])]
+/// fn dsp(cx: dsp::Context) {
+///     cx.local.audio_interface.handle_interrupt(|block| process(block)).unwrap();
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_audio_interrupt {
+    (sai2, $name:ident) => {
+        const _: () = assert!(
+            matches!(daisy::audio::SAI2_INTERRUPT, daisy::pac::Interrupt::$name),
+            concat!(stringify!($name), " is not the SAI2 audio interrupt")
+        );
+    };
+    ($name:ident) => {
+        const _: () = assert!(
+            matches!(daisy::audio::INTERRUPT, daisy::pac::Interrupt::$name),
+            concat!(stringify!($name), " is not the audio interrupt")
+        );
+    };
+}
+
 /// Allow access to the on-board LED.
 #[macro_export]
 macro_rules! board_split_leds {
@@ -371,6 +659,7 @@ macro_rules! board_split_flash {
             $ccdr.peripheral.QSPI,
             $pins.FLASH,
         )
+        .unwrap()
     }};
 }
 
@@ -12,8 +12,13 @@ use hal::time::MegaHertz;
 
 use crate::audio;
 
-// SAI clock uses pll3
-const PLL3_P: Hertz = Hertz::from_raw(audio::FS.to_Hz() * 256);
+// SAI clock uses pll3, running at 256 × fs
+const PLL3_P: Hertz = Hertz::from_raw(audio::SampleRate::Hz48000.frequency().to_Hz() * 256);
+
+// Parameters of PLL3 retuning, see RM0433
+const PLL3_M: u32 = 4; // 16 MHz crystal / 4 = 4 MHz reference clock
+const PLL3_VCO_MAX: u32 = 960_000_000; // wide range VCO, revision V
+const PLL3_FRACN_DIVISOR: u64 = 8192; // 2 ** 13
 
 pub trait SeedCrystal {
     const CRYSTAL_FREQ: MegaHertz = MegaHertz::from_raw(16);
@@ -30,6 +35,9 @@ impl SeedCrystal for rcc::Rcc {
 /// Configures the 16 MHz crystal, a 480 MHz system clock and PLL3 for
 /// SAI audio.
 ///
+/// PLL3 is initially tuned for 48 kHz. The audio interface retunes it on
+/// initialization to match the sampling rate selected in its configuration.
+///
 /// The Daisy Seed has a 16 MHz crystal wired to the MCU's high-speed
 /// external oscillator pins. We enable that, and use it to drive the
 /// full 480 MHz system clock.
@@ -58,3 +66,56 @@ pub fn configure(pwr: pwr::Pwr, rcc: rcc::Rcc, syscfg: &pac::SYSCFG) -> rcc::Ccd
 
     ccdr
 }
+
+/// Retune PLL3 so its P output runs at 256 × the given sampling rate.
+///
+/// The clock tree frozen by [`configure`] is not aware of this change, so its
+/// PLL3 frequency must not be used to derive audio clocks afterwards. This
+/// must be called while no peripheral clocked by PLL3 is running.
+///
+/// Returns the achieved PLL3 P frequency.
+pub(crate) fn configure_audio_pll(sample_rate: audio::SampleRate) -> Hertz {
+    let output = sample_rate.frequency().to_Hz() * 256;
+    let ref_ck = <rcc::Rcc as SeedCrystal>::CRYSTAL_FREQ.to_Hz() / PLL3_M;
+
+    // Choose the highest VCO frequency, use fractional N divider to get as
+    // close as possible to the requested frequency.
+    let divp = PLL3_VCO_MAX / output;
+    let vco_ck = output * divp;
+    let divn = vco_ck / ref_ck;
+    let fracn = ((vco_ck - divn * ref_ck) as u64 * PLL3_FRACN_DIVISOR / ref_ck as u64) as u16;
+
+    // Safety: PLL3 is only used by the audio interface which owns it at
+    // this point.
+    let rcc = unsafe { &*pac::RCC::ptr() };
+
+    rcc.cr.modify(|_, w| w.pll3on().off());
+    while rcc.cr.read().pll3rdy().is_ready() {}
+
+    rcc.pllckselr.modify(|_, w| w.divm3().bits(PLL3_M as u8));
+    rcc.pllcfgr.modify(|_, w| {
+        w.pll3vcosel()
+            .wide_vco()
+            .pll3rge()
+            .range4()
+            .pll3fracen()
+            .reset()
+            .divp3en()
+            .enabled()
+    });
+    rcc.pll3divr.modify(|_, w| unsafe {
+        w.divn3()
+            .bits((divn - 1) as u16)
+            .divp3()
+            .bits((divp - 1) as u8)
+    });
+    rcc.pll3fracr.modify(|_, w| w.fracn3().bits(fracn));
+    rcc.pllcfgr.modify(|_, w| w.pll3fracen().set());
+
+    rcc.cr.modify(|_, w| w.pll3on().on());
+    while rcc.cr.read().pll3rdy().is_not_ready() {}
+
+    let vco_ck =
+        ref_ck as u64 * (divn as u64 * PLL3_FRACN_DIVISOR + fracn as u64) / PLL3_FRACN_DIVISOR;
+    Hertz::from_raw((vco_ck / divp as u64) as u32)
+}
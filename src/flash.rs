@@ -1,9 +1,17 @@
 //! Basic driver for IS25LP064 providing access to the on-board flash storage.
+//!
+//! Besides its own API, [`Flash`] implements
+//! [`NorFlash`](embedded_storage::nor_flash::NorFlash) of `embedded-storage`,
+//! so it can be used with crates building on it. For fast reads of large
+//! data, such as wavetables or samples, the memory array can be mapped into
+//! the address space of the CPU through [`Flash::into_memory_mapped`].
 
 use crate::hal;
 use crate::hal::gpio::Speed;
 use crate::hal::prelude::*;
-use crate::hal::xspi::{Config, Qspi, QspiMode, QspiWord};
+use crate::hal::xspi::{Config, Qspi, QspiError, QspiMode, QspiWord};
+use embedded_storage::nor_flash::{self, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash};
+use hal::pac::{CorePeripherals, DWT};
 
 use crate::pins::FlashPins;
 
@@ -13,19 +21,64 @@ const WRITE_CMD: u8 = 0x02; // PP
 const READ_STATUS_REGISTRY_CMD: u8 = 0x05; // RDSR
 const WRITE_ENABLE_CMD: u8 = 0x06; // WREN
 const ENTER_QPI_MODE_CMD: u8 = 0x35; // QPIEN
+const BLOCK_ERASE_32K_CMD: u8 = 0x52; // BER32K
+const CHIP_ERASE_CMD: u8 = 0xC7; // CER
 const SET_READ_PARAMETERS_CMD: u8 = 0xC0; // SRP
 const SECTOR_ERASE_CMD: u8 = 0xD7; // SER
+const BLOCK_ERASE_64K_CMD: u8 = 0xD8; // BER64K
 const FAST_READ_QUAD_IO_CMD: u8 = 0xEB; // FRQIO
 
+// Bits of the status register.
+const STATUS_WRITE_IN_PROGRESS: u8 = 0b0000_0001; // WIP
+const STATUS_WRITE_ENABLE_LATCH: u8 = 0b0000_0010; // WEL
+const STATUS_BLOCK_PROTECTION: u8 = 0b0011_1100; // BP0-BP3
+
 // Memory array specifications as defined in the datasheet.
 const SECTOR_SIZE: u32 = 4096;
+const BLOCK_32K_SIZE: u32 = 32 * 1024;
+const BLOCK_64K_SIZE: u32 = 64 * 1024;
 const PAGE_SIZE: u32 = 256;
 const MAX_ADDRESS: u32 = 0x7FFFFF;
+const CAPACITY: usize = MAX_ADDRESS as usize + 1;
+
+// Start of the QUADSPI bank in the address space of the CPU.
+const MEMORY_MAPPED_ADDRESS: usize = 0x9000_0000;
+
+// Time limits of operations, with a margin above maximums from the datasheet.
+const REGISTER_WRITE_TIMEOUT_MS: u32 = 100;
+const PAGE_PROGRAM_TIMEOUT_MS: u32 = 10;
+const SECTOR_ERASE_TIMEOUT_MS: u32 = 1_000;
+const BLOCK_ERASE_32K_TIMEOUT_MS: u32 = 2_000;
+const BLOCK_ERASE_64K_TIMEOUT_MS: u32 = 3_000;
+const CHIP_ERASE_TIMEOUT_MS: u32 = 100_000;
+const QSPI_IDLE_TIMEOUT_MS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The address is outside the range of the memory.
+    OutOfRange,
+    /// Communication with the memory over QUADSPI failed.
+    Qspi,
+    /// The memory did not finish the operation in time.
+    Timeout,
+    /// The memory refused to be written to.
+    WriteProtected,
+    /// Erased range is not aligned to sectors, reported only through
+    /// [`NorFlash`].
+    NotAligned,
+}
+
+impl From<QspiError> for Error {
+    fn from(_: QspiError) -> Self {
+        Self::Qspi
+    }
+}
 
 /// Flash abstraction serves as a high level driver for the flash memory on the
 /// board.
 pub struct Flash {
     driver: Qspi<hal::pac::QUADSPI>,
+    cycles_per_ms: u32,
 }
 
 impl Flash {
@@ -41,7 +94,8 @@ impl Flash {
     /// let pins = daisy::board_split_gpios!(board, ccdr, dp);
     ///
     /// let mut flash =
-    ///     daisy::flash::Flash::new(&ccdr.clocks, dp.QUADSPI, ccdr.peripheral.QSPI, pins.FLASH);
+    ///     daisy::flash::Flash::new(&ccdr.clocks, dp.QUADSPI, ccdr.peripheral.QSPI, pins.FLASH)
+    ///         .unwrap();
     /// ```
     ///
     /// See more under the `examples/` directory.
@@ -50,7 +104,7 @@ impl Flash {
         qspi_device: hal::device::QUADSPI,
         qspi_peripheral: hal::rcc::rec::Qspi,
         pins: FlashPins,
-    ) -> Self {
+    ) -> Result<Self, Error> {
         // Even though it is not directly used, CS pin must be acquired and configured.
         let mut cs = pins.CS.into_alternate::<10>();
         let mut sck = pins.SCK.into_alternate::<9>();
@@ -73,13 +127,23 @@ impl Flash {
             qspi_peripheral,
         );
 
-        let mut flash = Self { driver: qspi };
+        // Timeouts are measured by the cycle counter. Safety: Only enabling
+        // trace and cycle counter, which is idempotent and does not interfere
+        // with other users.
+        let mut cp = unsafe { CorePeripherals::steal() };
+        cp.DCB.enable_trace();
+        cp.DWT.enable_cycle_counter();
+
+        let mut flash = Self {
+            driver: qspi,
+            cycles_per_ms: clocks.sys_ck().to_Hz() / 1000,
+        };
 
-        flash.enable_qpi_mode();
-        flash.reset_status_register();
-        flash.reset_read_register();
+        flash.enable_qpi_mode()?;
+        flash.reset_status_register()?;
+        flash.reset_read_register()?;
 
-        flash
+        Ok(flash)
     }
 
     /// Blocking polling read.
@@ -88,46 +152,93 @@ impl Flash {
     /// reaches end of the array, it would wrap around and continue reading from
     /// the beginning.
     ///
-    /// # Panics
-    ///
-    /// Panics if the address is outside the range of the memory.
-    pub fn read(&mut self, address: u32, buffer: &mut [u8]) {
-        assert!(address <= MAX_ADDRESS);
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), Error> {
+        check_address(address)?;
 
         // Data must be queried by chunks of 32 (limitation of `read_extended`)
         for (i, chunk) in buffer.chunks_mut(32).enumerate() {
-            self.driver
-                .read_extended(
-                    QspiWord::U8(FAST_READ_QUAD_IO_CMD),
-                    QspiWord::U24(address + i as u32 * 32),
-                    QspiWord::U8(0x00),
-                    8,
-                    chunk,
-                )
-                .unwrap();
+            self.driver.read_extended(
+                QspiWord::U8(FAST_READ_QUAD_IO_CMD),
+                QspiWord::U24(address + i as u32 * 32),
+                QspiWord::U8(0x00),
+                8,
+                chunk,
+            )?;
         }
+
+        Ok(())
     }
 
     /// Blocking polling write.
     ///
     /// This method would write data of arbitrary length, starting on given
     /// address. In case end of the memory array is reached, it will wrap around
-    /// and continue from the beginning.
+    /// and continue from the beginning. Empty data are ignored.
     ///
     /// It is important to note that every memory sector that will be written to
     /// will get completelly erased, no matter where in it the address is.
-    /// Sectors are aligned to 4 Kbytes.
+    /// Sectors are aligned to 4 Kbytes. Use [`Flash::write_preserving`] to
+    /// keep the rest of the sectors.
     ///
-    /// # Panics
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn write(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        check_address(address)?;
+
+        self.erase(address, data.len() as u32)?;
+        self.program(address, data)
+    }
+
+    /// Blocking polling write keeping the rest of affected sectors intact.
     ///
-    /// Panics if the address is outside the range of the memory.
+    /// Works like [`Flash::write`], except that parts of the first and last
+    /// sector which are not written to are read first and programmed back
+    /// after the erase. This is slower, but allows updating small records
+    /// without destroying their neighbours.
     ///
-    /// Panics if data is empty.
-    pub fn write(&mut self, mut address: u32, data: &[u8]) {
-        assert!(address <= MAX_ADDRESS);
-        assert!(!data.is_empty());
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn write_preserving(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        check_address(address)?;
+
+        let mut buffer = [0; SECTOR_SIZE as usize];
+        let mut data = data;
+
+        for span in sector_spans(address, data.len()) {
+            // Only sectors that are not completely overwritten need reading.
+            if span.size < SECTOR_SIZE as usize {
+                self.read(span.sector, &mut buffer)?;
+            }
+            buffer[span.offset..span.offset + span.size].copy_from_slice(&data[..span.size]);
+
+            self.erase(span.sector, SECTOR_SIZE)?;
+            self.program(span.sector, &buffer)?;
+
+            data = &data[span.size..];
+        }
 
-        self.erase(address, data.len() as u32);
+        Ok(())
+    }
+
+    /// Blocking polling write without erasing.
+    ///
+    /// Programming can only flip bits from 1 to 0, so the written area is
+    /// expected to be erased already, e.g. when appending to a log. Otherwise
+    /// the stored data end up as a bitwise AND of the old and new content.
+    ///
+    /// Data are programmed page-by-page, wrapping around at the end of the
+    /// memory array like [`Flash::write`]. Empty data are ignored.
+    ///
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn program(&mut self, mut address: u32, data: &[u8]) -> Result<(), Error> {
+        check_address(address)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.check_protection()?;
 
         let mut length = data.len() as u32;
         let mut start_cursor = 0;
@@ -142,16 +253,14 @@ impl Flash {
                 .chunks(32)
                 .enumerate()
             {
-                self.enable_write();
-                self.driver
-                    .write_extended(
-                        QspiWord::U8(WRITE_CMD),
-                        QspiWord::U24(address + i as u32 * 32),
-                        QspiWord::None,
-                        chunk,
-                    )
-                    .unwrap();
-                self.wait_for_write();
+                self.enable_write()?;
+                self.driver.write_extended(
+                    QspiWord::U8(WRITE_CMD),
+                    QspiWord::U24(address + i as u32 * 32),
+                    QspiWord::None,
+                    chunk,
+                )?;
+                self.wait_for_write(PAGE_PROGRAM_TIMEOUT_MS)?;
             }
             start_cursor += size;
 
@@ -163,8 +272,10 @@ impl Flash {
 
             // Jump to the next page.
             address += page_remainder;
-            address %= MAX_ADDRESS;
+            address &= MAX_ADDRESS;
         }
+
+        Ok(())
     }
 
     /// Erase `length` bytes at address `address` sector-by-sector and replace
@@ -174,31 +285,26 @@ impl Flash {
     /// middle of a sector, the whole sector will be removed.
     ///
     /// If the length overlaps the end of the memory array, the method will wrap
-    /// to the beginning and continue erasing there.
+    /// to the beginning and continue erasing there. Zero length is ignored.
     ///
     /// Learn more about the memory structure in the datasheet.
     ///
-    /// # Panics
-    ///
-    /// Panics if the address is outside the range of the memory.
-    ///
-    /// Panics if length is zero.
-    pub fn erase(&mut self, mut address: u32, mut length: u32) {
-        assert!(address <= MAX_ADDRESS);
-        assert!(length > 0);
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn erase(&mut self, mut address: u32, mut length: u32) -> Result<(), Error> {
+        check_address(address)?;
+        if length == 0 {
+            return Ok(());
+        }
+        self.check_protection()?;
 
         loop {
             // Erase the sector.
-            self.enable_write();
-            self.driver
-                .write_extended(
-                    QspiWord::U8(SECTOR_ERASE_CMD),
-                    QspiWord::U24(address),
-                    QspiWord::None,
-                    &[],
-                )
-                .unwrap();
-            self.wait_for_write();
+            self.erase_with(
+                SECTOR_ERASE_CMD,
+                QspiWord::U24(address),
+                SECTOR_ERASE_TIMEOUT_MS,
+            )?;
 
             // Calculate number of bytes between address and end of the sector.
             let sector_remainder = SECTOR_SIZE - (address & (SECTOR_SIZE - 1));
@@ -211,94 +317,560 @@ impl Flash {
 
             // Jump to the next sector.
             address += sector_remainder;
-            address %= MAX_ADDRESS;
+            address &= MAX_ADDRESS;
+        }
+
+        Ok(())
+    }
+
+    /// Erase the whole 32 Kbyte block containing the address and replace it
+    /// with 0xFF.
+    ///
+    /// This is much faster than erasing its sectors one by one.
+    ///
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn erase_block_32k(&mut self, address: u32) -> Result<(), Error> {
+        check_address(address)?;
+        self.check_protection()?;
+        self.erase_with(
+            BLOCK_ERASE_32K_CMD,
+            QspiWord::U24(address & !(BLOCK_32K_SIZE - 1)),
+            BLOCK_ERASE_32K_TIMEOUT_MS,
+        )
+    }
+
+    /// Erase the whole 64 Kbyte block containing the address and replace it
+    /// with 0xFF.
+    ///
+    /// This is much faster than erasing its sectors one by one.
+    ///
+    /// Returns [`Error::OutOfRange`] if the address is outside the range of
+    /// the memory.
+    pub fn erase_block_64k(&mut self, address: u32) -> Result<(), Error> {
+        check_address(address)?;
+        self.check_protection()?;
+        self.erase_with(
+            BLOCK_ERASE_64K_CMD,
+            QspiWord::U24(address & !(BLOCK_64K_SIZE - 1)),
+            BLOCK_ERASE_64K_TIMEOUT_MS,
+        )
+    }
+
+    /// Erase the whole memory array and replace it with 0xFF.
+    ///
+    /// This blocks for tens of seconds.
+    pub fn erase_chip(&mut self) -> Result<(), Error> {
+        self.check_protection()?;
+        self.erase_with(CHIP_ERASE_CMD, QspiWord::None, CHIP_ERASE_TIMEOUT_MS)
+    }
+
+    /// Switch the QUADSPI peripheral into memory-mapped mode, serving reads
+    /// of the CPU from address 0x90000000 with Fast Read Quad I/O commands.
+    ///
+    /// The memory array cannot be written in this mode. Use
+    /// [`MemoryMappedFlash::into_indirect`] to get back.
+    ///
+    /// Returns [`Error::Timeout`] together with the unchanged handle if the
+    /// peripheral does not finish its ongoing transaction in time.
+    pub fn into_memory_mapped(mut self) -> Result<MemoryMappedFlash, (Flash, Error)> {
+        if let Err(error) = self.wait_for_idle() {
+            return Err((self, error));
         }
+
+        // The mapped region is cacheable, its cached content may be stale
+        // after writes done in indirect mode. Safety: Only cache maintenance
+        // is done, which does not interfere with other users.
+        let mut cp = unsafe { CorePeripherals::steal() };
+        cp.SCB.clean_invalidate_dcache(&mut cp.CPUID);
+
+        // Mirror the indirect read done by `read`, with four lines used for
+        // all phases, 24-bit address, zeroed mode byte and 8 dummy cycles.
+        let regs = self.driver.inner_mut();
+        regs.abr.write(|w| unsafe { w.alternate().bits(0x00) });
+        regs.ccr.write(|w| unsafe {
+            w.fmode()
+                .bits(0b11) // memory-mapped mode
+                .instruction()
+                .bits(FAST_READ_QUAD_IO_CMD)
+                .imode()
+                .bits(0b11)
+                .admode()
+                .bits(0b11)
+                .adsize()
+                .bits(0b10) // 24-bit address
+                .abmode()
+                .bits(0b11)
+                .absize()
+                .bits(0b00) // 8-bit mode byte
+                .dcyc()
+                .bits(8)
+                .dmode()
+                .bits(0b11)
+        });
+
+        Ok(MemoryMappedFlash { flash: self })
+    }
+
+    /// Send an erase command and wait until it is done.
+    fn erase_with(&mut self, command: u8, address: QspiWord, timeout_ms: u32) -> Result<(), Error> {
+        self.enable_write()?;
+        self.driver
+            .write_extended(QspiWord::U8(command), address, QspiWord::None, &[])?;
+        self.wait_for_write(timeout_ms)
     }
 
     /// Reset status registers into driver's defaults. This makes sure that the
     /// peripheral is configured as expected.
-    fn reset_status_register(&mut self) {
-        self.enable_write();
+    fn reset_status_register(&mut self) -> Result<(), Error> {
+        self.enable_write()?;
 
-        self.driver
-            .write_extended(
-                QspiWord::U8(WRITE_STATUS_REGISTRY_CMD),
-                QspiWord::U8(0b0000_0010),
-                QspiWord::None,
-                &[],
-            )
-            .unwrap();
+        self.driver.write_extended(
+            QspiWord::U8(WRITE_STATUS_REGISTRY_CMD),
+            QspiWord::U8(0b0000_0010),
+            QspiWord::None,
+            &[],
+        )?;
 
-        self.wait_for_write();
+        self.wait_for_write(REGISTER_WRITE_TIMEOUT_MS)
     }
 
     /// Reset read registers into driver's defaults. This makes sure that the
     /// peripheral is configured as expected.
-    fn reset_read_register(&mut self) {
-        self.enable_write();
+    fn reset_read_register(&mut self) -> Result<(), Error> {
+        self.enable_write()?;
 
-        self.driver
-            .write_extended(
-                QspiWord::U8(SET_READ_PARAMETERS_CMD),
-                QspiWord::U8(0b1111_1000),
-                QspiWord::None,
-                &[],
-            )
-            .unwrap();
+        self.driver.write_extended(
+            QspiWord::U8(SET_READ_PARAMETERS_CMD),
+            QspiWord::U8(0b1111_1000),
+            QspiWord::None,
+            &[],
+        )?;
 
-        self.wait_for_write();
+        self.wait_for_write(REGISTER_WRITE_TIMEOUT_MS)
     }
 
     /// Enable quad mode to allow for faster reading.
-    fn enable_qpi_mode(&mut self) {
-        self.enable_write();
+    fn enable_qpi_mode(&mut self) -> Result<(), Error> {
+        // The memory may be already in QPI mode, not understanding the
+        // status read in single-bit mode, so the latch is not verified.
+        self.send_write_enable()?;
 
-        self.driver
-            .write_extended(
-                QspiWord::U8(ENTER_QPI_MODE_CMD),
-                QspiWord::None,
-                QspiWord::None,
-                &[],
-            )
-            .unwrap();
+        self.driver.write_extended(
+            QspiWord::U8(ENTER_QPI_MODE_CMD),
+            QspiWord::None,
+            QspiWord::None,
+            &[],
+        )?;
 
-        self.driver.configure_mode(QspiMode::FourBit).unwrap();
+        self.driver.configure_mode(QspiMode::FourBit)?;
 
-        self.wait_for_write();
+        self.wait_for_write(REGISTER_WRITE_TIMEOUT_MS)
     }
 
     /// Allow write operation. This call is required before each and every
     /// command writting to the memory or registers as well as erasing memory.
-    fn enable_write(&mut self) {
-        self.driver
-            .write_extended(
-                QspiWord::U8(WRITE_ENABLE_CMD),
-                QspiWord::None,
-                QspiWord::None,
-                &[],
-            )
-            .unwrap();
+    ///
+    /// Returns [`Error::WriteProtected`] if the memory did not latch the
+    /// write enable.
+    fn enable_write(&mut self) -> Result<(), Error> {
+        self.send_write_enable()?;
+
+        if self.read_status()? & STATUS_WRITE_ENABLE_LATCH == 0 {
+            return Err(Error::WriteProtected);
+        }
+
+        Ok(())
+    }
+
+    fn send_write_enable(&mut self) -> Result<(), Error> {
+        self.driver.write_extended(
+            QspiWord::U8(WRITE_ENABLE_CMD),
+            QspiWord::None,
+            QspiWord::None,
+            &[],
+        )?;
+        Ok(())
+    }
+
+    /// Verify that no part of the memory array is protected by block
+    /// protection bits, which would make it silently ignore writes.
+    fn check_protection(&mut self) -> Result<(), Error> {
+        if self.read_status()? & STATUS_BLOCK_PROTECTION != 0 {
+            return Err(Error::WriteProtected);
+        }
+        Ok(())
     }
 
     /// Wait for write operation to finish. This method is blocking and will
     /// poll WIP bit in status register. It can be used to monitor both write
     /// progress into memory as well as erasing and changes in registers.
-    fn wait_for_write(&mut self) {
+    ///
+    /// Returns [`Error::Timeout`] if the operation takes longer than
+    /// `timeout_ms` milliseconds.
+    fn wait_for_write(&mut self, timeout_ms: u32) -> Result<(), Error> {
+        self.wait_until(timeout_ms, |flash| {
+            Ok(flash.read_status()? & STATUS_WRITE_IN_PROGRESS == 0)
+        })
+    }
+
+    /// Wait for the QUADSPI peripheral to finish its ongoing transaction.
+    ///
+    /// Returns [`Error::Timeout`] if it stays busy for too long.
+    fn wait_for_idle(&mut self) -> Result<(), Error> {
+        self.wait_until(QSPI_IDLE_TIMEOUT_MS, |flash| {
+            Ok(flash.driver.is_busy().is_ok())
+        })
+    }
+
+    /// Poll `done` until it returns true, or return [`Error::Timeout`] after
+    /// `timeout_ms` milliseconds.
+    fn wait_until(
+        &mut self,
+        timeout_ms: u32,
+        mut done: impl FnMut(&mut Self) -> Result<bool, Error>,
+    ) -> Result<(), Error> {
+        // Cycles are accumulated in 64 bits, as the counter wraps around in
+        // seconds.
+        let timeout = timeout_ms as u64 * self.cycles_per_ms as u64;
+        let mut elapsed: u64 = 0;
+        let mut last = DWT::cycle_count();
+
         loop {
-            let mut status: [u8; 1] = [0xFF; 1];
-            self.driver
-                .read_extended(
-                    QspiWord::U8(READ_STATUS_REGISTRY_CMD),
-                    QspiWord::None,
-                    QspiWord::None,
-                    0,
-                    &mut status,
-                )
-                .unwrap();
+            if done(self)? {
+                return Ok(());
+            }
 
-            if status[0] & 0x01 == 0 {
-                break;
+            let now = DWT::cycle_count();
+            elapsed += now.wrapping_sub(last) as u64;
+            last = now;
+            if elapsed > timeout {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    fn read_status(&mut self) -> Result<u8, Error> {
+        let mut status: [u8; 1] = [0xFF; 1];
+        self.driver.read_extended(
+            QspiWord::U8(READ_STATUS_REGISTRY_CMD),
+            QspiWord::None,
+            QspiWord::None,
+            0,
+            &mut status,
+        )?;
+        Ok(status[0])
+    }
+}
+
+/// Flash memory mapped into the address space of the CPU, created by
+/// [`Flash::into_memory_mapped`].
+pub struct MemoryMappedFlash {
+    flash: Flash,
+}
+
+impl MemoryMappedFlash {
+    /// The whole memory array, read directly by the CPU.
+    pub fn data(&self) -> &[u8] {
+        // Safety: The QUADSPI peripheral stays in memory-mapped mode while
+        // the slice is borrowed, serving reads of the whole array.
+        unsafe { core::slice::from_raw_parts(MEMORY_MAPPED_ADDRESS as *const u8, CAPACITY) }
+    }
+
+    /// Keep the memory mapped forever, returning the whole memory array.
+    pub fn leak(self) -> &'static [u8] {
+        // Safety: There is no way back to indirect mode without the handle.
+        unsafe { core::slice::from_raw_parts(MEMORY_MAPPED_ADDRESS as *const u8, CAPACITY) }
+    }
+
+    /// Switch the QUADSPI peripheral back into indirect mode, allowing
+    /// writes.
+    ///
+    /// Returns [`Error::Timeout`] together with the handle if the peripheral
+    /// does not become idle in time. The switch can be then retried.
+    pub fn into_indirect(mut self) -> Result<Flash, (MemoryMappedFlash, Error)> {
+        // Abort the ongoing memory-mapped read. Indirect transactions set
+        // their mode on their own.
+        let regs = self.flash.driver.inner_mut();
+        regs.cr.modify(|_, w| w.abort().set_bit());
+
+        if let Err(error) = self.flash.wait_for_idle() {
+            return Err((self, error));
+        }
+
+        Ok(self.flash)
+    }
+}
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::OutOfRange => NorFlashErrorKind::OutOfBounds,
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl From<NorFlashErrorKind> for Error {
+    fn from(kind: NorFlashErrorKind) -> Self {
+        match kind {
+            NorFlashErrorKind::NotAligned => Self::NotAligned,
+            _ => Self::OutOfRange,
+        }
+    }
+}
+
+impl nor_flash::ErrorType for Flash {
+    type Error = Error;
+}
+
+impl ReadNorFlash for Flash {
+    const READ_SIZE: usize = 1;
+
+    /// Read without wrapping around the end of the memory array.
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        nor_flash::check_read(self, offset, bytes.len())?;
+        Flash::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        CAPACITY
+    }
+}
+
+impl NorFlash for Flash {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE as usize;
+
+    /// Erase sectors in range `from..to`, using block erase for aligned
+    /// blocks within it.
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        nor_flash::check_erase(self, from, to)?;
+
+        for (address, unit) in erase_steps(from, to) {
+            match unit {
+                EraseUnit::Block64K => self.erase_block_64k(address)?,
+                EraseUnit::Block32K => self.erase_block_32k(address)?,
+                EraseUnit::Sector => Flash::erase(self, address, SECTOR_SIZE)?,
             }
         }
+
+        Ok(())
+    }
+
+    /// Program without erasing and without wrapping around the end of the
+    /// memory array.
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        nor_flash::check_write(self, offset, bytes.len())?;
+        self.program(offset, bytes)
+    }
+}
+
+fn check_address(address: u32) -> Result<(), Error> {
+    if address > MAX_ADDRESS {
+        return Err(Error::OutOfRange);
+    }
+    Ok(())
+}
+
+/// Part of a sector covered by written data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SectorSpan {
+    /// Address of the start of the sector.
+    sector: u32,
+    /// Offset of the data within the sector.
+    offset: usize,
+    size: usize,
+}
+
+/// Split `length` bytes starting at `address` into parts of the sectors they
+/// fall into, wrapping around at the end of the memory array.
+fn sector_spans(address: u32, length: usize) -> impl Iterator<Item = SectorSpan> {
+    let mut sector = address & !(SECTOR_SIZE - 1);
+    let mut offset = (address - sector) as usize;
+    let mut remaining = length;
+
+    core::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+
+        let size = (SECTOR_SIZE as usize - offset).min(remaining);
+        let span = SectorSpan {
+            sector,
+            offset,
+            size,
+        };
+
+        remaining -= size;
+        offset = 0;
+        sector = (sector + SECTOR_SIZE) & MAX_ADDRESS;
+
+        Some(span)
+    })
+}
+
+/// Area removed by a single erase command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EraseUnit {
+    Block64K,
+    Block32K,
+    Sector,
+}
+
+impl EraseUnit {
+    fn size(self) -> u32 {
+        match self {
+            Self::Block64K => BLOCK_64K_SIZE,
+            Self::Block32K => BLOCK_32K_SIZE,
+            Self::Sector => SECTOR_SIZE,
+        }
+    }
+}
+
+/// Split the sector-aligned range `from..to` into the largest aligned units
+/// fitting into it, returning their start addresses.
+fn erase_steps(from: u32, to: u32) -> impl Iterator<Item = (u32, EraseUnit)> {
+    let mut address = from;
+
+    core::iter::from_fn(move || {
+        if address >= to {
+            return None;
+        }
+
+        let remaining = to - address;
+        let unit = [EraseUnit::Block64K, EraseUnit::Block32K]
+            .into_iter()
+            .find(|unit| address.is_multiple_of(unit.size()) && remaining >= unit.size())
+            .unwrap_or(EraseUnit::Sector);
+        let step = (address, unit);
+        address += unit.size();
+
+        Some(step)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    fn span(sector: u32, offset: usize, size: usize) -> SectorSpan {
+        SectorSpan {
+            sector,
+            offset,
+            size,
+        }
+    }
+
+    #[test]
+    fn write_within_sector_is_a_single_span() {
+        let spans: Vec<_> = sector_spans(0x1010, 16).collect();
+        assert_eq!(spans, [span(0x1000, 0x10, 16)]);
+    }
+
+    #[test]
+    fn write_of_whole_sector_is_a_single_span() {
+        let spans: Vec<_> = sector_spans(0x2000, 4096).collect();
+        assert_eq!(spans, [span(0x2000, 0, 4096)]);
+    }
+
+    #[test]
+    fn unaligned_write_spanning_sectors_is_split_into_first_middle_and_last() {
+        let spans: Vec<_> = sector_spans(0x1F00, 0x100 + 2 * 4096 + 0x20).collect();
+        assert_eq!(
+            spans,
+            [
+                span(0x1000, 0xF00, 0x100),
+                span(0x2000, 0, 4096),
+                span(0x3000, 0, 4096),
+                span(0x4000, 0, 0x20),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_ending_at_capacity_does_not_wrap() {
+        let spans: Vec<_> = sector_spans(0x7FF800, 0x800).collect();
+        assert_eq!(spans, [span(0x7FF000, 0x800, 0x800)]);
+
+        let spans: Vec<_> = sector_spans(0x7FE800, 0x800 + 4096).collect();
+        assert_eq!(
+            spans,
+            [span(0x7FE000, 0x800, 0x800), span(0x7FF000, 0, 4096)]
+        );
+    }
+
+    #[test]
+    fn write_over_the_end_wraps_to_the_beginning() {
+        let spans: Vec<_> = sector_spans(0x7FFF00, 0x200).collect();
+        assert_eq!(spans, [span(0x7FF000, 0xF00, 0x100), span(0, 0, 0x100)]);
+    }
+
+    #[test]
+    fn empty_write_has_no_spans() {
+        assert_eq!(sector_spans(0x1234, 0).count(), 0);
+    }
+
+    #[test]
+    fn aligned_erase_uses_64k_blocks() {
+        let steps: Vec<_> = erase_steps(0x10000, 0x30000).collect();
+        assert_eq!(
+            steps,
+            [
+                (0x10000, EraseUnit::Block64K),
+                (0x20000, EraseUnit::Block64K)
+            ]
+        );
+    }
+
+    #[test]
+    fn unaligned_erase_uses_sectors_up_to_block_boundaries() {
+        let steps: Vec<_> = erase_steps(0x6000, 0x21000).collect();
+        assert_eq!(
+            steps,
+            [
+                (0x6000, EraseUnit::Sector),
+                (0x7000, EraseUnit::Sector),
+                (0x8000, EraseUnit::Block32K),
+                (0x10000, EraseUnit::Block64K),
+                (0x20000, EraseUnit::Sector),
+            ]
+        );
+    }
+
+    #[test]
+    fn erase_shorter_than_block_uses_sectors() {
+        let steps: Vec<_> = erase_steps(0x10000, 0x12000).collect();
+        assert_eq!(
+            steps,
+            [(0x10000, EraseUnit::Sector), (0x11000, EraseUnit::Sector)]
+        );
+    }
+
+    #[test]
+    fn erase_ending_at_capacity_stops_there() {
+        let capacity = CAPACITY as u32;
+        let steps: Vec<_> = erase_steps(capacity - 0x19000, capacity).collect();
+        assert_eq!(
+            steps,
+            [
+                (0x7E7000, EraseUnit::Sector),
+                (0x7E8000, EraseUnit::Block32K),
+                (0x7F0000, EraseUnit::Block64K),
+            ]
+        );
+
+        let steps: Vec<_> = erase_steps(0, capacity).collect();
+        assert_eq!(steps.len(), 128);
+        assert!(steps.iter().all(|&(_, unit)| unit == EraseUnit::Block64K));
+    }
+
+    #[test]
+    fn empty_erase_has_no_steps() {
+        assert_eq!(erase_steps(0x4000, 0x4000).count(), 0);
     }
 }
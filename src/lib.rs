@@ -14,18 +14,23 @@
 //! * [Daisy Seed 1.1](https://www.electro-smith.com/daisy/daisy) (codec WM8731), `seed_1_1`
 //! * [Daisy Seed 1.2](https://www.electro-smith.com/daisy/daisy) (codec PCM3060), `seed_1_2`
 //! * [Daisy Patch SM](https://www.electro-smith.com/daisy/patch-sm) (codec PCM3060), `patch_sm`
+//! * [Daisy Patch](https://www.electro-smith.com/daisy/patch) (additional codec AK4556), `patch`
 //!
-//! Select the board by using its respective feature.
+//! Select the board by using its respective feature. Daisy Patch carries a
+//! Seed, so its feature must be combined with the one of the Seed, e.g.
+//! `features = ["seed_1_1", "patch"]`.
 //!
 //! # Sampling rate
 //!
-//! By default, the audio sampling rate is set to 48 kHz. This can be increased to
-//! 96 kHz by enabling the `sampling_rate_96khz` feature.
+//! By default, the audio sampling rate is set to 48 kHz. Rates of 32, 44.1,
+//! 88.2 and 96 kHz can be selected at runtime through `audio::Config` passed
+//! to `board_split_audio!`.
 //!
 //! # Block length
 //!
-//! By default, the audio block length is 32 frames. This can be increased to 64
-//! by enabling the `block_length_64` feature.
+//! By default, the audio block length is 32 frames. Any even length between 4 and
+//! 256 frames can be selected through the const generic parameter of
+//! `audio::Interface`, e.g. `audio::Interface<64>`.
 //!
 //! # API stability
 //!
@@ -58,6 +63,7 @@
 //! * [Blinky with RTIC](https://github.com/zlosynth/daisy/blob/main/examples/blinky_rtic.rs)
 //! * [Flash storage](https://github.com/zlosynth/daisy/blob/main/examples/flash.rs)
 //! * [OLED display](https://github.com/zlosynth/daisy/blob/main/examples/oled.rs)
+//! * [Patch four-channel audio](https://github.com/zlosynth/daisy/blob/main/examples/patch.rs)
 //! * [SDRAM memory](https://github.com/zlosynth/daisy/blob/main/examples/sdram.rs)
 //! * [SD card](https://github.com/zlosynth/daisy/blob/main/examples/sdmmc.rs)
 //!
@@ -99,6 +105,14 @@ compile_error!(
     "target board must be selected using a feature: \"seed_1_2\" | \"seed_1_1\" | \"seed\" | \"patch_sm\""
 );
 
+#[cfg(all(
+    feature = "patch",
+    not(any(feature = "seed", feature = "seed_1_1", feature = "seed_1_2"))
+))]
+compile_error!(
+    "\"patch\" must be combined with the feature of the Seed it carries: \"seed_1_2\" | \"seed_1_1\" | \"seed\""
+);
+
 pub mod audio;
 pub mod board;
 pub mod clocks;